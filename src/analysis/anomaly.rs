@@ -0,0 +1,228 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use crate::analysis::stats::{file_stats, FileStats};
+use crate::analysis::{time_ordered, MetricTable};
+use crate::error::BridgeError;
+use crate::transformer::BridgeParsedAssignment;
+
+/// How a rule decides whether a metric value is anomalous.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleKind {
+    /// Alert when the value falls outside `[min, max]`.
+    Threshold { min: Option<f64>, max: Option<f64> },
+    /// Alert when the value differs from the mean of the previous `baseline`
+    /// files by more than `max_change_pct` percent.
+    PercentChange { max_change_pct: f64, baseline: usize },
+    /// Alert when the value is more than `max_z` standard deviations away from
+    /// the previous `baseline` files.
+    ZScore { max_z: f64, baseline: usize },
+}
+
+/// A single detection rule applied to one metric.
+///
+/// The metric may end in `.*` (e.g. `distributor.*`) to apply the rule to every
+/// value of that dimension seen in any of the files.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    pub metric: String,
+    #[serde(flatten)]
+    pub kind: RuleKind,
+}
+
+/// The set of rules to evaluate, usually loaded from a JSON file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RuleSet {
+    pub rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    pub fn from_file(path: &Path) -> Result<Self, BridgeError> {
+        let text = fs::read_to_string(path)?;
+        serde_json::from_str(&text)
+            .map_err(|e| BridgeError::Config(format!("Invalid rules file {}: {}", path.display(), e)))
+    }
+}
+
+impl Default for RuleSet {
+    /// Catch a distributor running empty and the functional share halving.
+    fn default() -> Self {
+        RuleSet {
+            rules: vec![
+                Rule {
+                    name: "empty-distributor".into(),
+                    metric: "distributor.*".into(),
+                    kind: RuleKind::Threshold { min: Some(1.0), max: None },
+                },
+                Rule {
+                    name: "functional-share-change".into(),
+                    metric: "functional_share".into(),
+                    kind: RuleKind::PercentChange { max_change_pct: 50.0, baseline: 7 },
+                },
+            ],
+        }
+    }
+}
+
+/// A rule violation for one metric in one file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Alert {
+    pub rule: String,
+    pub metric: String,
+    pub file_sha: String,
+    pub published: i64,
+    pub value: f64,
+    pub baseline: Option<f64>,
+    pub score: Option<f64>,
+    pub message: String,
+}
+
+/// All alerts raised over a set of files, oldest first.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AnomalyReport {
+    pub alerts: Vec<Alert>,
+}
+
+/// Evaluate every rule against the per-file statistics of the given assignments.
+///
+/// Baseline rules only look at files published before the one being checked,
+/// and are skipped until enough history is available. Each alert is also
+/// emitted as a `warn` tracing event.
+pub fn detect_anomalies(data: &[BridgeParsedAssignment], rules: &RuleSet) -> Result<AnomalyReport, BridgeError> {
+    let stats: Vec<FileStats> = time_ordered(data).into_iter().map(file_stats).collect();
+    let mut alerts = Vec::new();
+
+    for rule in &rules.rules {
+        for metric in expand_metric(&rule.metric, &stats) {
+            for (idx, current) in stats.iter().enumerate() {
+                if let Some(alert) = evaluate(rule, &metric, &stats[..idx], current) {
+                    warn!(
+                        rule = %alert.rule,
+                        metric = %alert.metric,
+                        file_sha = %alert.file_sha,
+                        published = alert.published,
+                        value = alert.value,
+                        "Anomaly detected: {}",
+                        alert.message
+                    );
+                    alerts.push(alert);
+                }
+            }
+        }
+    }
+
+    alerts.sort_by_key(|a| a.published);
+    Ok(AnomalyReport { alerts })
+}
+
+impl AnomalyReport {
+    /// Flatten the report into tables for export.
+    pub fn tables(&self) -> Vec<MetricTable> {
+        let mut table = MetricTable::new(
+            "alerts",
+            &["rule", "metric", "file_sha", "published", "value", "baseline", "score", "message"],
+        );
+        for alert in &self.alerts {
+            table.push(vec![
+                alert.rule.as_str().into(),
+                alert.metric.as_str().into(),
+                alert.file_sha.as_str().into(),
+                alert.published.into(),
+                alert.value.into(),
+                alert.baseline.into(),
+                alert.score.into(),
+                alert.message.as_str().into(),
+            ]);
+        }
+        vec![table]
+    }
+}
+
+/// Resolve a `dimension.*` pattern into the concrete metrics present in any file.
+fn expand_metric(pattern: &str, stats: &[FileStats]) -> Vec<String> {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => stats
+            .iter()
+            .flat_map(|s| s.metrics.keys())
+            .filter(|k| k.starts_with(prefix))
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect(),
+        None => vec![pattern.to_string()],
+    }
+}
+
+fn evaluate(rule: &Rule, metric: &str, history: &[FileStats], current: &FileStats) -> Option<Alert> {
+    let value = current.get(metric);
+    let alert = |baseline: Option<f64>, score: Option<f64>, message: String| Alert {
+        rule: rule.name.clone(),
+        metric: metric.to_string(),
+        file_sha: current.file_sha.clone(),
+        published: current.published,
+        value,
+        baseline,
+        score,
+        message,
+    };
+
+    match rule.kind {
+        RuleKind::Threshold { min, max } => {
+            if let Some(min) = min.filter(|m| value < *m) {
+                return Some(alert(None, None, format!("{} = {} is below minimum {}", metric, value, min)));
+            }
+            if let Some(max) = max.filter(|m| value > *m) {
+                return Some(alert(None, None, format!("{} = {} is above maximum {}", metric, value, max)));
+            }
+            None
+        }
+        RuleKind::PercentChange { max_change_pct, baseline } => {
+            let window = baseline_window(history, baseline, metric, 1)?;
+            let mean = mean(&window);
+            let change = if mean == 0.0 {
+                if value == 0.0 { 0.0 } else { f64::INFINITY }
+            } else {
+                (value - mean) / mean * 100.0
+            };
+            (change.abs() > max_change_pct).then(|| {
+                alert(
+                    Some(mean),
+                    Some(change),
+                    format!("{} = {} changed {:.1}% against baseline {:.4}", metric, value, change, mean),
+                )
+            })
+        }
+        RuleKind::ZScore { max_z, baseline } => {
+            let window = baseline_window(history, baseline, metric, 2)?;
+            let mean = mean(&window);
+            let variance = window.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (window.len() - 1) as f64;
+            let stddev = variance.sqrt();
+            if stddev == 0.0 {
+                return None;
+            }
+            let z = (value - mean) / stddev;
+            (z.abs() > max_z).then(|| {
+                alert(
+                    Some(mean),
+                    Some(z),
+                    format!("{} = {} has z-score {:.2} against baseline {:.4}", metric, value, z, mean),
+                )
+            })
+        }
+    }
+}
+
+/// Values of `metric` in the last `size` files, or `None` if fewer than `min` are available.
+fn baseline_window(history: &[FileStats], size: usize, metric: &str, min: usize) -> Option<Vec<f64>> {
+    let start = history.len().saturating_sub(size);
+    let window: Vec<f64> = history[start..].iter().map(|s| s.get(metric)).collect();
+    (window.len() >= min.max(1)).then_some(window)
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
@@ -0,0 +1,334 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use serde::Deserialize;
+use tracing::warn;
+use crate::analysis::{time_ordered, MetricTable};
+use crate::error::BridgeError;
+use crate::transformer::BridgeParsedAssignment;
+
+/// Expected share of the pool per distributor, loaded from a JSON file such as
+/// `{"proportions": {"email": 0.1, "https": 0.4, "moat": 0.2, ...}}`.
+///
+/// Proportions are normalised, so percentages or weights work as well as fractions.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BalanceConfig {
+    pub proportions: BTreeMap<String, f64>,
+    /// p-value below which a file's distribution is considered off balance.
+    #[serde(default = "default_significance")]
+    pub significance: f64,
+    /// Minimum relative deviation (in percent) for a distributor to be flagged.
+    #[serde(default = "default_tolerance_pct")]
+    pub tolerance_pct: f64,
+}
+
+fn default_significance() -> f64 {
+    0.05
+}
+
+fn default_tolerance_pct() -> f64 {
+    10.0
+}
+
+impl BalanceConfig {
+    pub fn from_file(path: &Path) -> Result<Self, BridgeError> {
+        let text = fs::read_to_string(path)?;
+        let config: BalanceConfig = serde_json::from_str(&text)
+            .map_err(|e| BridgeError::Config(format!("Invalid balance config {}: {}", path.display(), e)))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), BridgeError> {
+        if self.proportions.values().any(|p| !p.is_finite() || *p < 0.0) {
+            return Err(BridgeError::Config("Distributor proportions must be non-negative".into()));
+        }
+        if self.proportions.values().sum::<f64>() <= 0.0 {
+            return Err(BridgeError::Config("Distributor proportions must not all be zero".into()));
+        }
+        Ok(())
+    }
+
+    /// Proportions scaled to sum to one.
+    fn normalised(&self) -> BTreeMap<&str, f64> {
+        let sum: f64 = self.proportions.values().sum();
+        self.proportions.iter().map(|(k, v)| (k.as_str(), v / sum)).collect()
+    }
+}
+
+/// Whether a distributor holds about the share it should.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provisioning {
+    Ok,
+    Over,
+    Under,
+    /// The distributor has bridges but no configured proportion.
+    Unexpected,
+}
+
+impl Provisioning {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Provisioning::Ok => "ok",
+            Provisioning::Over => "over",
+            Provisioning::Under => "under",
+            Provisioning::Unexpected => "unexpected",
+        }
+    }
+}
+
+/// Observed versus expected bridges for one distributor in one file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistributorBalance {
+    pub distributor: String,
+    pub observed: usize,
+    pub expected: f64,
+    pub deviation_pct: Option<f64>,
+    pub status: Provisioning,
+}
+
+/// Goodness-of-fit of one file's distribution against the expected proportions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileBalance {
+    pub file_sha: String,
+    pub published: i64,
+    pub total: usize,
+    pub chi_square: f64,
+    pub degrees_of_freedom: usize,
+    pub p_value: f64,
+    pub significant: bool,
+    pub distributors: Vec<DistributorBalance>,
+}
+
+/// Balance checks for every file, oldest first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceReport {
+    pub files: Vec<FileBalance>,
+}
+
+impl BalanceReport {
+    /// Number of over-, under-provisioned or unexpected distributors over all files.
+    pub fn flagged(&self) -> usize {
+        self.files
+            .iter()
+            .flat_map(|f| &f.distributors)
+            .filter(|d| d.status != Provisioning::Ok)
+            .count()
+    }
+
+    /// Flatten the report into tables for export.
+    pub fn tables(&self) -> Vec<MetricTable> {
+        let mut files = MetricTable::new(
+            "balance",
+            &["file_sha", "published", "total", "chi_square", "degrees_of_freedom", "p_value", "significant"],
+        );
+        let mut distributors = MetricTable::new(
+            "balance_distributors",
+            &["file_sha", "published", "distributor", "observed", "expected", "deviation_pct", "status"],
+        );
+
+        for file in &self.files {
+            files.push(vec![
+                file.file_sha.as_str().into(),
+                file.published.into(),
+                file.total.into(),
+                file.chi_square.into(),
+                file.degrees_of_freedom.into(),
+                file.p_value.into(),
+                file.significant.into(),
+            ]);
+            for d in &file.distributors {
+                distributors.push(vec![
+                    file.file_sha.as_str().into(),
+                    file.published.into(),
+                    d.distributor.as_str().into(),
+                    d.observed.into(),
+                    d.expected.into(),
+                    d.deviation_pct.into(),
+                    d.status.as_str().into(),
+                ]);
+            }
+        }
+
+        vec![files, distributors]
+    }
+}
+
+/// Compare each file's distributor counts against the configured proportions.
+///
+/// A Pearson chi-square test is run over the configured distributors. When the
+/// result is significant, every distributor whose count deviates from its
+/// expectation by more than `tolerance_pct` is flagged as over- or
+/// under-provisioned. Distributors without a configured proportion are always
+/// flagged as unexpected and left out of the test.
+pub fn check_balance(data: &[BridgeParsedAssignment], config: &BalanceConfig) -> Result<BalanceReport, BridgeError> {
+    config.validate()?;
+    let proportions = config.normalised();
+    let mut files = Vec::new();
+
+    for snapshot in time_ordered(data) {
+        let mut observed: BTreeMap<&str, usize> = proportions.keys().map(|k| (*k, 0)).collect();
+        for line in &snapshot.lines {
+            *observed.entry(line.distribution_method.as_str()).or_default() += 1;
+        }
+
+        let total: usize = observed
+            .iter()
+            .filter(|(k, _)| proportions.contains_key(*k))
+            .map(|(_, n)| n)
+            .sum();
+
+        let mut chi_square = 0.0;
+        let mut cells: usize = 0;
+        let mut distributors = Vec::new();
+        for (distributor, count) in observed {
+            let Some(share) = proportions.get(distributor) else {
+                distributors.push(DistributorBalance {
+                    distributor: distributor.to_string(),
+                    observed: count,
+                    expected: 0.0,
+                    deviation_pct: None,
+                    status: Provisioning::Unexpected,
+                });
+                continue;
+            };
+
+            let expected = share * total as f64;
+            if expected > 0.0 {
+                chi_square += (count as f64 - expected).powi(2) / expected;
+                cells += 1;
+            }
+            distributors.push(DistributorBalance {
+                distributor: distributor.to_string(),
+                observed: count,
+                expected,
+                deviation_pct: (expected > 0.0).then(|| (count as f64 - expected) / expected * 100.0),
+                status: Provisioning::Ok,
+            });
+        }
+
+        let degrees_of_freedom = cells.saturating_sub(1);
+        let p_value = if degrees_of_freedom == 0 {
+            1.0
+        } else {
+            chi_square_p_value(chi_square, degrees_of_freedom as f64)
+        };
+        let significant = p_value < config.significance;
+
+        for d in distributors.iter_mut() {
+            match d.deviation_pct {
+                Some(dev) if significant && dev > config.tolerance_pct => d.status = Provisioning::Over,
+                Some(dev) if significant && dev < -config.tolerance_pct => d.status = Provisioning::Under,
+                None if d.status == Provisioning::Ok && d.observed > 0 => d.status = Provisioning::Over,
+                _ => {}
+            }
+            if d.status != Provisioning::Ok {
+                warn!(
+                    file_sha = %snapshot.file_sha,
+                    distributor = %d.distributor,
+                    observed = d.observed,
+                    expected = d.expected,
+                    "Distributor is {}-provisioned",
+                    d.status.as_str()
+                );
+            }
+        }
+
+        files.push(FileBalance {
+            file_sha: snapshot.file_sha.clone(),
+            published: snapshot.published,
+            total,
+            chi_square,
+            degrees_of_freedom,
+            p_value,
+            significant,
+            distributors,
+        });
+    }
+
+    Ok(BalanceReport { files })
+}
+
+/// Upper-tail probability of the chi-square distribution, `Q(k/2, x/2)`.
+fn chi_square_p_value(x: f64, k: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    upper_regularized_gamma(k / 2.0, x / 2.0)
+}
+
+/// Regularized upper incomplete gamma function, using the series expansion for
+/// small `x` and a continued fraction otherwise (Numerical Recipes, 6.2).
+fn upper_regularized_gamma(a: f64, x: f64) -> f64 {
+    const EPS: f64 = 1e-14;
+    const MAX_ITER: usize = 500;
+    let ln_prefix = a * x.ln() - x - ln_gamma(a);
+
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        let mut n = a;
+        for _ in 0..MAX_ITER {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * EPS {
+                break;
+            }
+        }
+        (1.0 - sum * ln_prefix.exp()).clamp(0.0, 1.0)
+    } else {
+        let tiny = f64::MIN_POSITIVE / EPS;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..=MAX_ITER {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < EPS {
+                break;
+            }
+        }
+        (h * ln_prefix.exp()).clamp(0.0, 1.0)
+    }
+}
+
+/// Natural log of the gamma function (Lanczos approximation, g = 7).
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+
+    if x < 0.5 {
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+
+    let x = x - 1.0;
+    let mut sum = COEFFS[0];
+    for (i, c) in COEFFS.iter().enumerate().skip(1) {
+        sum += c / (x + i as f64);
+    }
+    let t = x + 7.5;
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
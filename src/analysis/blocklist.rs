@@ -0,0 +1,129 @@
+use std::collections::{BTreeMap, HashSet};
+use chrono::NaiveDate;
+use crate::analysis::{time_ordered, transport_key, MetricTable, Window};
+use crate::error::BridgeError;
+use crate::transformer::BridgeParsedAssignment;
+
+/// Number of bridges blocked in one country, per distributor and transport.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockedCount {
+    pub window_start: NaiveDate,
+    pub country: String,
+    pub distributor: String,
+    pub transport: String,
+    pub blocked: usize,
+}
+
+/// A bridge that appeared on a country's blocklist since the previous snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewlyBlocked {
+    pub published: i64,
+    pub fingerprint: String,
+    pub country: String,
+    pub distributor: String,
+    pub transport: String,
+}
+
+/// Blocking per country over time, plus the bridges that became newly blocked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlocklistReport {
+    pub window: Window,
+    pub blocked: Vec<BlockedCount>,
+    pub newly_blocked: Vec<NewlyBlocked>,
+}
+
+/// Analyse the validated blocklist countries of a set of parsed assignments.
+///
+/// Blocked counts describe the pool at the end of each window, i.e. they are
+/// taken from the latest snapshot published in that window. Newly blocked
+/// bridges are found by comparing consecutive snapshots, so the first snapshot
+/// only sets the baseline.
+pub fn analyze_blocklist(data: &[BridgeParsedAssignment], window: Window) -> Result<BlocklistReport, BridgeError> {
+    let snapshots = time_ordered(data);
+
+    let mut latest_per_window = BTreeMap::new();
+    for snapshot in &snapshots {
+        latest_per_window.insert(window.bucket(snapshot.published)?, *snapshot);
+    }
+
+    let mut blocked = Vec::new();
+    for (window_start, snapshot) in latest_per_window {
+        let mut counts: BTreeMap<(String, &str, String), usize> = BTreeMap::new();
+        for line in &snapshot.lines {
+            for country in line.blocked_countries() {
+                let key = (country, line.distribution_method.as_str(), transport_key(line.transport.as_deref()));
+                *counts.entry(key).or_default() += 1;
+            }
+        }
+        blocked.extend(counts.into_iter().map(|((country, distributor, transport), n)| BlockedCount {
+            window_start,
+            country,
+            distributor: distributor.to_string(),
+            transport,
+            blocked: n,
+        }));
+    }
+
+    let mut newly_blocked = Vec::new();
+    let mut previous: Option<HashSet<(&str, String)>> = None;
+    for snapshot in &snapshots {
+        let mut current = HashSet::new();
+        for line in &snapshot.lines {
+            for country in line.blocked_countries() {
+                let key = (line.fingerprint.as_str(), country);
+                if previous.as_ref().is_some_and(|p| !p.contains(&key)) {
+                    newly_blocked.push(NewlyBlocked {
+                        published: snapshot.published,
+                        fingerprint: line.fingerprint.clone(),
+                        country: key.1.clone(),
+                        distributor: line.distribution_method.clone(),
+                        transport: transport_key(line.transport.as_deref()),
+                    });
+                }
+                current.insert(key);
+            }
+        }
+        previous = Some(current);
+    }
+
+    Ok(BlocklistReport {
+        window,
+        blocked,
+        newly_blocked,
+    })
+}
+
+impl BlocklistReport {
+    /// Flatten the report into tables for export.
+    pub fn tables(&self) -> Vec<MetricTable> {
+        let mut blocked = MetricTable::new(
+            "blocked",
+            &["window_start", "country", "distributor", "transport", "blocked"],
+        );
+        for row in &self.blocked {
+            blocked.push(vec![
+                row.window_start.to_string().into(),
+                row.country.as_str().into(),
+                row.distributor.as_str().into(),
+                row.transport.as_str().into(),
+                row.blocked.into(),
+            ]);
+        }
+
+        let mut newly = MetricTable::new(
+            "newly_blocked",
+            &["published", "fingerprint", "country", "distributor", "transport"],
+        );
+        for row in &self.newly_blocked {
+            newly.push(vec![
+                row.published.into(),
+                row.fingerprint.as_str().into(),
+                row.country.as_str().into(),
+                row.distributor.as_str().into(),
+                row.transport.as_str().into(),
+            ]);
+        }
+
+        vec![blocked, newly]
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::{BTreeMap, HashMap};
+use chrono::NaiveDate;
+use crate::analysis::{time_ordered, transport_key, MetricTable, Window};
+use crate::error::BridgeError;
+use crate::transformer::BridgeParsedAssignment;
+
+const MILLIS_PER_HOUR: f64 = 3_600_000.0;
+
+/// Bridges that joined and left one distributor within one window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistributorChurn {
+    pub window_start: NaiveDate,
+    pub distributor: String,
+    pub joined: usize,
+    pub left: usize,
+}
+
+/// Lifetime statistics for the bridges of one transport (or "all").
+#[derive(Debug, Clone, PartialEq)]
+pub struct LifetimeSummary {
+    pub transport: String,
+    pub spans: usize,
+    pub ended: usize,
+    pub median_lifetime_hours: Option<f64>,
+}
+
+/// One step of a Kaplan-Meier survival curve.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurvivalPoint {
+    pub transport: String,
+    pub duration_hours: f64,
+    pub at_risk: usize,
+    pub events: usize,
+    pub survival: f64,
+}
+
+/// Churn and lifetime metrics computed over a sequence of snapshots.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChurnReport {
+    pub window: Window,
+    pub churn: Vec<DistributorChurn>,
+    pub lifetimes: Vec<LifetimeSummary>,
+    pub survival: Vec<SurvivalPoint>,
+}
+
+/// A continuous stretch of snapshots in which a bridge was in the pool.
+struct Span {
+    transport: String,
+    duration_ms: i64,
+    ended: bool,
+}
+
+/// Compute churn, lifetimes and survival curves from a set of parsed assignments.
+///
+/// Snapshots are ordered by publication time. The first snapshot only sets the
+/// baseline, so joins and leaves are counted from the second snapshot on and
+/// attributed to the window of the snapshot in which the change was observed.
+/// A bridge moving from one distributor to another counts as a leave from the
+/// old one and a join to the new one.
+///
+/// A bridge's lifetime runs from the first snapshot it appears in until the
+/// first snapshot it is missing from; bridges still present in the last
+/// snapshot are treated as censored in the survival curves.
+pub fn analyze_churn(data: &[BridgeParsedAssignment], window: Window) -> Result<ChurnReport, BridgeError> {
+    let snapshots = time_ordered(data);
+
+    let mut counts: BTreeMap<(NaiveDate, String), (usize, usize)> = BTreeMap::new();
+    let mut previous: Option<HashMap<&str, &str>> = None;
+    let mut open: HashMap<&str, (i64, String)> = HashMap::new();
+    let mut spans = Vec::new();
+
+    for snapshot in &snapshots {
+        let current: HashMap<&str, &str> = snapshot
+            .lines
+            .iter()
+            .map(|l| (l.fingerprint.as_str(), l.distribution_method.as_str()))
+            .collect();
+
+        if let Some(prev) = &previous {
+            let bucket = window.bucket(snapshot.published)?;
+            for (fingerprint, method) in &current {
+                if prev.get(fingerprint) != Some(method) {
+                    counts.entry((bucket, method.to_string())).or_default().0 += 1;
+                }
+            }
+            for (fingerprint, method) in prev {
+                if current.get(fingerprint) != Some(method) {
+                    counts.entry((bucket, method.to_string())).or_default().1 += 1;
+                }
+            }
+        }
+
+        open.retain(|fingerprint, (start, transport)| {
+            if current.contains_key(fingerprint) {
+                return true;
+            }
+            spans.push(Span {
+                transport: std::mem::take(transport),
+                duration_ms: snapshot.published - *start,
+                ended: true,
+            });
+            false
+        });
+
+        for line in &snapshot.lines {
+            open.entry(line.fingerprint.as_str()).or_insert_with(|| {
+                (snapshot.published, transport_key(line.transport.as_deref()))
+            });
+        }
+
+        previous = Some(current);
+    }
+
+    if let Some(last) = snapshots.last() {
+        for (_, (start, transport)) in open {
+            spans.push(Span {
+                transport,
+                duration_ms: last.published - start,
+                ended: false,
+            });
+        }
+    }
+
+    let churn = counts
+        .into_iter()
+        .map(|((window_start, distributor), (joined, left))| DistributorChurn {
+            window_start,
+            distributor,
+            joined,
+            left,
+        })
+        .collect();
+
+    let mut by_transport: BTreeMap<&str, Vec<&Span>> = BTreeMap::new();
+    for span in &spans {
+        by_transport.entry(span.transport.as_str()).or_default().push(span);
+    }
+    by_transport.insert("all", spans.iter().collect());
+
+    let mut lifetimes = Vec::new();
+    let mut survival = Vec::new();
+    for (transport, group) in by_transport {
+        let total = group.len();
+        let ended = group.iter().filter(|s| s.ended).count();
+        let (curve, median) = kaplan_meier(transport, group);
+        lifetimes.push(LifetimeSummary {
+            transport: transport.to_string(),
+            spans: total,
+            ended,
+            median_lifetime_hours: median,
+        });
+        survival.extend(curve);
+    }
+
+    Ok(ChurnReport {
+        window,
+        churn,
+        lifetimes,
+        survival,
+    })
+}
+
+impl ChurnReport {
+    /// Flatten the report into tables for export.
+    pub fn tables(&self) -> Vec<MetricTable> {
+        let mut churn = MetricTable::new("churn", &["window_start", "distributor", "joined", "left"]);
+        for row in &self.churn {
+            churn.push(vec![
+                row.window_start.to_string().into(),
+                row.distributor.as_str().into(),
+                row.joined.into(),
+                row.left.into(),
+            ]);
+        }
+
+        let mut lifetime = MetricTable::new(
+            "lifetime",
+            &["transport", "spans", "ended", "median_lifetime_hours"],
+        );
+        for row in &self.lifetimes {
+            lifetime.push(vec![
+                row.transport.as_str().into(),
+                row.spans.into(),
+                row.ended.into(),
+                row.median_lifetime_hours.into(),
+            ]);
+        }
+
+        let mut survival = MetricTable::new(
+            "survival",
+            &["transport", "duration_hours", "at_risk", "events", "survival"],
+        );
+        for row in &self.survival {
+            survival.push(vec![
+                row.transport.as_str().into(),
+                row.duration_hours.into(),
+                row.at_risk.into(),
+                row.events.into(),
+                row.survival.into(),
+            ]);
+        }
+
+        vec![churn, lifetime, survival]
+    }
+}
+
+/// Kaplan-Meier estimate over the given spans, returning the curve and the median lifetime.
+fn kaplan_meier(transport: &str, mut spans: Vec<&Span>) -> (Vec<SurvivalPoint>, Option<f64>) {
+    spans.sort_by_key(|s| s.duration_ms);
+
+    let mut curve = Vec::new();
+    let mut median = None;
+    let mut at_risk = spans.len();
+    let mut survival = 1.0;
+    let mut i = 0;
+
+    while i < spans.len() {
+        let duration = spans[i].duration_ms;
+        let mut events = 0;
+        let mut removed = 0;
+        while i < spans.len() && spans[i].duration_ms == duration {
+            if spans[i].ended {
+                events += 1;
+            }
+            removed += 1;
+            i += 1;
+        }
+
+        if events > 0 {
+            survival *= 1.0 - events as f64 / at_risk as f64;
+            let hours = duration as f64 / MILLIS_PER_HOUR;
+            curve.push(SurvivalPoint {
+                transport: transport.to_string(),
+                duration_hours: hours,
+                at_risk,
+                events,
+                survival,
+            });
+            if median.is_none() && survival <= 0.5 {
+                median = Some(hours);
+            }
+        }
+        at_risk -= removed;
+    }
+
+    (curve, median)
+}
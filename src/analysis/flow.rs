@@ -0,0 +1,187 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use chrono::{Duration, NaiveDate};
+use serde::Serialize;
+use crate::analysis::{time_ordered, MetricTable};
+use crate::error::BridgeError;
+use crate::exporter::{AtomicFile, IfExists};
+use crate::transformer::BridgeParsedAssignment;
+
+/// Pseudo-distributor for bridges that were not in the earlier snapshot.
+pub const ENTERED_POOL: &str = "entered pool";
+/// Pseudo-distributor for bridges that are gone from the later snapshot.
+pub const LEFT_POOL: &str = "left pool";
+
+/// Number of bridges that went from one distributor to another between two snapshots.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowEdge {
+    /// Index of the earlier snapshot in [`FlowReport::stages`].
+    pub step: usize,
+    pub from_published: i64,
+    pub to_published: i64,
+    pub source: String,
+    pub target: String,
+    pub bridges: usize,
+}
+
+/// Bridge flows between successive stages (snapshots), oldest first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowReport {
+    pub stages: Vec<i64>,
+    pub edges: Vec<FlowEdge>,
+}
+
+/// Node of a D3 Sankey diagram; one per distributor and stage.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SankeyNode {
+    pub name: String,
+    pub stage: usize,
+    pub published: i64,
+}
+
+/// Link of a D3 Sankey diagram, referencing nodes by index.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SankeyLink {
+    pub source: usize,
+    pub target: usize,
+    pub value: usize,
+}
+
+/// Graph in the `{nodes, links}` shape expected by `d3-sankey`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Sankey {
+    pub nodes: Vec<SankeyNode>,
+    pub links: Vec<SankeyLink>,
+}
+
+/// Count bridge moves between distributors.
+///
+/// Without a date range every pair of consecutive snapshots forms one step.
+/// With `between = Some((from, to))` a single step is built from the first
+/// snapshot published on or after `from` to the last one published on or
+/// before `to` (both dates inclusive, UTC).
+pub fn analyze_flows(
+    data: &[BridgeParsedAssignment],
+    between: Option<(NaiveDate, NaiveDate)>,
+) -> Result<FlowReport, BridgeError> {
+    let mut snapshots = time_ordered(data);
+
+    if let Some((from, to)) = between {
+        let start = from.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc().timestamp_millis();
+        let end = (to + Duration::days(1)).and_hms_opt(0, 0, 0).unwrap_or_default().and_utc().timestamp_millis();
+        let in_range: Vec<_> = snapshots
+            .into_iter()
+            .filter(|s| s.published >= start && s.published < end)
+            .collect();
+        snapshots = match (in_range.first(), in_range.last()) {
+            (Some(first), Some(last)) if in_range.len() > 1 => vec![*first, *last],
+            _ => {
+                return Err(BridgeError::Config(format!(
+                    "Need at least two snapshots published between {} and {}",
+                    from, to
+                )))
+            }
+        };
+    }
+
+    let mut edges = Vec::new();
+    for (step, pair) in snapshots.windows(2).enumerate() {
+        let (before, after) = (pair[0], pair[1]);
+        let old: HashMap<&str, &str> = before
+            .lines
+            .iter()
+            .map(|l| (l.fingerprint.as_str(), l.distribution_method.as_str()))
+            .collect();
+        let new: HashMap<&str, &str> = after
+            .lines
+            .iter()
+            .map(|l| (l.fingerprint.as_str(), l.distribution_method.as_str()))
+            .collect();
+
+        let mut counts: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+        for (fingerprint, method) in &old {
+            let target = new.get(fingerprint).copied().unwrap_or(LEFT_POOL);
+            *counts.entry((method, target)).or_default() += 1;
+        }
+        for (fingerprint, method) in &new {
+            if !old.contains_key(fingerprint) {
+                *counts.entry((ENTERED_POOL, method)).or_default() += 1;
+            }
+        }
+
+        edges.extend(counts.into_iter().map(|((source, target), bridges)| FlowEdge {
+            step,
+            from_published: before.published,
+            to_published: after.published,
+            source: source.to_string(),
+            target: target.to_string(),
+            bridges,
+        }));
+    }
+
+    Ok(FlowReport {
+        stages: snapshots.iter().map(|s| s.published).collect(),
+        edges,
+    })
+}
+
+impl FlowReport {
+    /// Flatten the report into an edge-list table for export.
+    pub fn tables(&self) -> Vec<MetricTable> {
+        let mut table = MetricTable::new(
+            "flows",
+            &["from_published", "to_published", "source", "target", "bridges"],
+        );
+        for edge in &self.edges {
+            table.push(vec![
+                edge.from_published.into(),
+                edge.to_published.into(),
+                edge.source.as_str().into(),
+                edge.target.as_str().into(),
+                edge.bridges.into(),
+            ]);
+        }
+        vec![table]
+    }
+
+    /// Build a multi-stage Sankey graph with one column of nodes per snapshot.
+    ///
+    /// "entered pool" nodes sit in the earlier stage of a step and "left pool"
+    /// nodes in the later one, so the graph stays acyclic.
+    pub fn to_sankey<'a>(&'a self) -> Sankey {
+        let mut index: HashMap<(usize, &'a str), usize> = HashMap::new();
+        let mut nodes = Vec::new();
+        let mut links = Vec::new();
+
+        for edge in &self.edges {
+            let mut node = |stage: usize, name: &'a str| {
+                *index.entry((stage, name)).or_insert_with(|| {
+                    nodes.push(SankeyNode {
+                        name: name.to_string(),
+                        stage,
+                        published: self.stages[stage],
+                    });
+                    nodes.len() - 1
+                })
+            };
+            let source = node(edge.step, &edge.source);
+            let target = node(edge.step + 1, &edge.target);
+            links.push(SankeyLink {
+                source,
+                target,
+                value: edge.bridges,
+            });
+        }
+
+        Sankey { nodes, links }
+    }
+
+    /// Write the Sankey graph as JSON; the file only appears once it is complete.
+    pub fn write_sankey_json(&self, path: &Path, if_exists: IfExists) -> Result<(), BridgeError> {
+        let mut file = AtomicFile::create(path, if_exists)?;
+        serde_json::to_writer_pretty(&mut file, &self.to_sankey())
+            .map_err(|e| BridgeError::Export(format!("Failed to write Sankey JSON: {}", e)))?;
+        file.commit()?;
+        Ok(())
+    }
+}
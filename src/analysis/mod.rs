@@ -0,0 +1,77 @@
+// Analyses that run over a time-ordered sequence of parsed assignments
+pub mod anomaly;
+pub mod balance;
+pub mod blocklist;
+pub mod churn;
+pub mod flow;
+pub mod stats;
+pub mod table;
+
+pub use anomaly::{detect_anomalies, Alert, AnomalyReport, RuleSet};
+pub use balance::{check_balance, BalanceConfig, BalanceReport};
+pub use blocklist::{analyze_blocklist, BlocklistReport};
+pub use churn::{analyze_churn, ChurnReport};
+pub use flow::{analyze_flows, FlowReport, Sankey};
+pub use stats::{file_stats, FileStats};
+pub use table::{MetricTable, MetricValue};
+
+use std::str::FromStr;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use crate::error::BridgeError;
+use crate::helper::millis_to_utc;
+use crate::transformer::BridgeParsedAssignment;
+
+/// Aggregation window used to bucket snapshots by their publication time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl Window {
+    /// Return the first day of the window containing the given millisecond timestamp.
+    /// Weekly windows start on Monday.
+    pub fn bucket(&self, millis: i64) -> Result<NaiveDate, BridgeError> {
+        let date = to_utc(millis)?.date_naive();
+        Ok(match self {
+            Window::Daily => date,
+            Window::Weekly => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            Window::Monthly => date.with_day(1).unwrap_or(date),
+        })
+    }
+}
+
+impl FromStr for Window {
+    type Err = BridgeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "daily" | "day" => Ok(Window::Daily),
+            "weekly" | "week" => Ok(Window::Weekly),
+            "monthly" | "month" => Ok(Window::Monthly),
+            other => Err(BridgeError::Parse(format!(
+                "Unknown window '{}', expected daily, weekly or monthly",
+                other
+            ))),
+        }
+    }
+}
+
+/// Return the assignments ordered by publication time (oldest first).
+pub fn time_ordered(data: &[BridgeParsedAssignment]) -> Vec<&BridgeParsedAssignment> {
+    let mut ordered: Vec<&BridgeParsedAssignment> = data.iter().collect();
+    ordered.sort_by_key(|a| a.published);
+    ordered
+}
+
+/// Grouping key for an entry's transport; entries without one are "unknown".
+pub(crate) fn transport_key(transport: Option<&str>) -> String {
+    transport.unwrap_or("unknown").to_string()
+}
+
+/// Convert i64 timestamp in millis to a UTC DateTime.
+fn to_utc(ms: i64) -> Result<DateTime<Utc>, BridgeError> {
+    millis_to_utc(ms)
+        .ok_or_else(|| BridgeError::InvalidTimestamp(ms.to_string()))
+}
@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+use crate::transformer::BridgeParsedAssignment;
+
+/// Named per-file statistics, e.g. `total`, `functional_share` or `distributor.email`.
+///
+/// Count metrics are keyed `<dimension>.<value>` for the `distributor`,
+/// `transport`, `state` and `bandwidth` dimensions. A dimension value that does
+/// not occur in a file is simply absent and reads as zero through [`FileStats::get`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileStats {
+    pub file_sha: String,
+    pub published: i64,
+    pub metrics: BTreeMap<String, f64>,
+}
+
+impl FileStats {
+    /// Return the value of a metric, treating missing metrics as zero.
+    pub fn get(&self, metric: &str) -> f64 {
+        self.metrics.get(metric).copied().unwrap_or(0.0)
+    }
+}
+
+/// Compute the statistics of one assignment file.
+pub fn file_stats(assignment: &BridgeParsedAssignment) -> FileStats {
+    let mut metrics = BTreeMap::new();
+    let total = assignment.lines.len() as f64;
+    let mut functional = 0.0;
+    let mut distributed = 0.0;
+
+    for line in &assignment.lines {
+        *metrics.entry(format!("distributor.{}", line.distribution_method)).or_insert(0.0) += 1.0;
+        if let Some(transport) = &line.transport {
+            *metrics.entry(format!("transport.{}", transport)).or_insert(0.0) += 1.0;
+        }
+        if let Some(state) = &line.state {
+            *metrics.entry(format!("state.{}", state)).or_insert(0.0) += 1.0;
+            if state == "functional" {
+                functional += 1.0;
+            }
+        }
+        if let Some(bandwidth) = &line.bandwidth {
+            *metrics.entry(format!("bandwidth.{}", bandwidth)).or_insert(0.0) += 1.0;
+        }
+        if line.distributed == Some(true) {
+            distributed += 1.0;
+        }
+    }
+
+    metrics.insert("total".to_string(), total);
+    metrics.insert("functional".to_string(), functional);
+    metrics.insert("distributed".to_string(), distributed);
+    let share = |n: f64| if total > 0.0 { n / total } else { 0.0 };
+    metrics.insert("functional_share".to_string(), share(functional));
+    metrics.insert("distributed_share".to_string(), share(distributed));
+
+    FileStats {
+        file_sha: assignment.file_sha.clone(),
+        published: assignment.published,
+        metrics,
+    }
+}
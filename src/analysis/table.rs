@@ -0,0 +1,89 @@
+use std::fmt;
+
+/// A single cell in an analysis result table.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetricValue {
+    Text(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Null,
+}
+
+impl fmt::Display for MetricValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetricValue::Text(s) => write!(f, "{}", s),
+            MetricValue::Int(i) => write!(f, "{}", i),
+            MetricValue::Float(v) => write!(f, "{}", v),
+            MetricValue::Bool(b) => write!(f, "{}", b),
+            MetricValue::Null => Ok(()),
+        }
+    }
+}
+
+impl From<&str> for MetricValue {
+    fn from(s: &str) -> Self {
+        MetricValue::Text(s.to_string())
+    }
+}
+
+impl From<String> for MetricValue {
+    fn from(s: String) -> Self {
+        MetricValue::Text(s)
+    }
+}
+
+impl From<i64> for MetricValue {
+    fn from(i: i64) -> Self {
+        MetricValue::Int(i)
+    }
+}
+
+impl From<usize> for MetricValue {
+    fn from(i: usize) -> Self {
+        MetricValue::Int(i as i64)
+    }
+}
+
+impl From<f64> for MetricValue {
+    fn from(v: f64) -> Self {
+        MetricValue::Float(v)
+    }
+}
+
+impl From<bool> for MetricValue {
+    fn from(b: bool) -> Self {
+        MetricValue::Bool(b)
+    }
+}
+
+impl<T: Into<MetricValue>> From<Option<T>> for MetricValue {
+    fn from(v: Option<T>) -> Self {
+        v.map_or(MetricValue::Null, Into::into)
+    }
+}
+
+/// A named, rectangular table of analysis results that exporters can write out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricTable {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<MetricValue>>,
+}
+
+impl MetricTable {
+    pub fn new(name: &str, columns: &[&str]) -> Self {
+        MetricTable {
+            name: name.to_string(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Append a row; it must have one value per column.
+    pub fn push(&mut self, row: Vec<MetricValue>) {
+        debug_assert_eq!(row.len(), self.columns.len(), "row width must match columns");
+        self.rows.push(row);
+    }
+}
@@ -99,7 +99,7 @@ fn extract_latest_entries(index: &Value, target_dir: &str, limit: usize) -> Resu
         files.push((full_path, millis));
     }
 
-    files.sort_by(|a, b| b.1.cmp(&a.1)); // newest first
+    files.sort_by_key(|f| std::cmp::Reverse(f.1)); // newest first
     Ok(files.into_iter().take(limit).collect())
 }
 
@@ -24,6 +24,8 @@ pub enum BridgeError {
     InvalidFingerprint(String),
     #[error("CSV error: {0}")]
     Csv(String),
+    #[error("Config error: {0}")]
+    Config(String),
 }
 
 impl From<std::io::Error> for BridgeError {
@@ -37,3 +39,10 @@ impl From<csv::Error> for BridgeError {
         BridgeError::Export(err.to_string())
     }
 }
+
+#[cfg(feature = "sqlite_export")]
+impl From<rusqlite::Error> for BridgeError {
+    fn from(err: rusqlite::Error) -> Self {
+        BridgeError::Database(err.to_string())
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use arrow::array::{
+    ArrayRef, BooleanBuilder, Float32Builder, ListBuilder, StringBuilder, StringDictionaryBuilder,
+    TimestampMillisecondBuilder, TimestampSecondBuilder, UInt8Builder,
+};
+use arrow::datatypes::{DataType, Field, Int32Type, Schema, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use crate::error::BridgeError;
+use crate::exporter::schema::{Column, ColumnSchema, ColumnValue, TimestampFormat};
+use crate::transformer::{BridgeLineEntry, BridgeParsedAssignment};
+
+/// Key/value metadata recorded in Arrow schemas: the tool version and, if
+/// known, where the assignments came from.
+pub(crate) fn metadata(source: Option<&str>) -> HashMap<String, String> {
+    let mut metadata = HashMap::from([(
+        "bridge_parser.version".to_string(),
+        env!("CARGO_PKG_VERSION").to_string(),
+    )]);
+    if let Some(source) = source {
+        metadata.insert("bridge_parser.source".to_string(), source.to_string());
+    }
+    metadata
+}
+
+/// Builds one Arrow column of a record batch, with the logical type of its column.
+enum ColumnBuilder {
+    Text(StringBuilder),
+    /// Categorical text, stored as `Dictionary<Int32, Utf8>`.
+    Category(StringDictionaryBuilder<Int32Type>),
+    Timestamp(TimestampMillisecondBuilder),
+    TimestampSeconds(TimestampSecondBuilder),
+    Bool(BooleanBuilder),
+    Float(Float32Builder),
+    /// `ip=4,6` as `List<UInt8>`.
+    IpVersions(ListBuilder<UInt8Builder>),
+    /// Validated blocklist country codes as `List<Utf8>`.
+    Countries(ListBuilder<StringBuilder>),
+}
+
+impl ColumnBuilder {
+    fn new(column: Column, format: TimestampFormat, dictionaries: bool) -> Self {
+        match column {
+            Column::Published if format == TimestampFormat::EpochSeconds => {
+                ColumnBuilder::TimestampSeconds(TimestampSecondBuilder::new().with_timezone("UTC"))
+            }
+            Column::Published => ColumnBuilder::Timestamp(TimestampMillisecondBuilder::new().with_timezone("UTC")),
+            Column::DistributionMethod | Column::Transport | Column::State | Column::Bandwidth if dictionaries => {
+                ColumnBuilder::Category(StringDictionaryBuilder::new())
+            }
+            Column::Distributed => ColumnBuilder::Bool(BooleanBuilder::new()),
+            Column::Ratio => ColumnBuilder::Float(Float32Builder::new()),
+            Column::Ip => ColumnBuilder::IpVersions(ListBuilder::new(UInt8Builder::new())),
+            Column::Blocklist => ColumnBuilder::Countries(ListBuilder::new(StringBuilder::new())),
+            Column::FileSha
+            | Column::Header
+            | Column::EntrySha
+            | Column::Fingerprint
+            | Column::DistributionMethod
+            | Column::Transport
+            | Column::State
+            | Column::Bandwidth => ColumnBuilder::Text(StringBuilder::new()),
+        }
+    }
+
+    fn data_type(&self) -> DataType {
+        match self {
+            ColumnBuilder::Text(_) => DataType::Utf8,
+            ColumnBuilder::Category(_) => DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+            ColumnBuilder::Timestamp(_) => DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+            ColumnBuilder::TimestampSeconds(_) => DataType::Timestamp(TimeUnit::Second, Some("UTC".into())),
+            ColumnBuilder::Bool(_) => DataType::Boolean,
+            ColumnBuilder::Float(_) => DataType::Float32,
+            ColumnBuilder::IpVersions(_) => DataType::List(Arc::new(Field::new("item", DataType::UInt8, true))),
+            ColumnBuilder::Countries(_) => DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+        }
+    }
+
+    fn append(&mut self, column: Column, file: &BridgeParsedAssignment, line: &BridgeLineEntry) {
+        match (self, column.value(file, line)) {
+            (ColumnBuilder::Text(b), ColumnValue::Text(t)) => b.append_option(t),
+            (ColumnBuilder::Category(b), ColumnValue::Text(t)) => b.append_option(t),
+            (ColumnBuilder::Timestamp(b), ColumnValue::Timestamp(ms)) => b.append_value(ms),
+            (ColumnBuilder::TimestampSeconds(b), ColumnValue::Timestamp(ms)) => b.append_value(ms.div_euclid(1000)),
+            (ColumnBuilder::Bool(b), ColumnValue::Bool(v)) => b.append_option(v),
+            (ColumnBuilder::Float(b), ColumnValue::Float(f)) => b.append_option(f),
+            (ColumnBuilder::IpVersions(b), ColumnValue::Text(ip)) => {
+                if ip.is_some() {
+                    b.values().append_slice(&line.ip_versions());
+                }
+                b.append(ip.is_some());
+            }
+            (ColumnBuilder::Countries(b), ColumnValue::Text(blocklist)) => {
+                if blocklist.is_some() {
+                    for country in line.blocked_countries() {
+                        b.values().append_value(country);
+                    }
+                }
+                b.append(blocklist.is_some());
+            }
+            (builder, value) => unreachable!("{:?} does not fit the {} column builder", value, builder.data_type()),
+        }
+    }
+
+    /// Take the buffered values, leaving the builder empty for the next batch.
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            ColumnBuilder::Text(b) => Arc::new(b.finish()),
+            ColumnBuilder::Category(b) => Arc::new(b.finish()),
+            ColumnBuilder::Timestamp(b) => Arc::new(b.finish()),
+            ColumnBuilder::TimestampSeconds(b) => Arc::new(b.finish()),
+            ColumnBuilder::Bool(b) => Arc::new(b.finish()),
+            ColumnBuilder::Float(b) => Arc::new(b.finish()),
+            ColumnBuilder::IpVersions(b) => Arc::new(b.finish()),
+            ColumnBuilder::Countries(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+/// Buffers entries column by column and turns them into record batches of
+/// the selected columns. Shared by the Parquet and Arrow IPC exporters.
+pub(crate) struct BatchBuilder {
+    schema: Arc<Schema>,
+    columns: ColumnSchema,
+    builders: Vec<ColumnBuilder>,
+    buffered: usize,
+}
+
+impl BatchBuilder {
+    /// `dictionaries` stores categorical columns dictionary-encoded; without
+    /// it they are plain `Utf8`.
+    pub(crate) fn new(columns: &ColumnSchema, metadata: HashMap<String, String>, dictionaries: bool) -> Self {
+        let builders: Vec<ColumnBuilder> = columns
+            .columns
+            .iter()
+            .map(|c| ColumnBuilder::new(*c, columns.timestamp_format, dictionaries))
+            .collect();
+        // IPC tracks dictionaries by id, so every dictionary column needs its own
+        let fields: Vec<Field> = columns
+            .columns
+            .iter()
+            .zip(&builders)
+            .enumerate()
+            .map(|(i, (c, b))| Field::new_dict(c.name(), b.data_type(), c.nullable(), i as i64, false))
+            .collect();
+
+        BatchBuilder {
+            schema: Arc::new(Schema::new(fields).with_metadata(metadata)),
+            columns: columns.clone(),
+            builders,
+            buffered: 0,
+        }
+    }
+
+    pub(crate) fn schema(&self) -> Arc<Schema> {
+        self.schema.clone()
+    }
+
+    /// Number of entries buffered since the last batch.
+    pub(crate) fn len(&self) -> usize {
+        self.buffered
+    }
+
+    pub(crate) fn append(&mut self, file: &BridgeParsedAssignment, line: &BridgeLineEntry) {
+        for (column, builder) in self.columns.columns.iter().zip(&mut self.builders) {
+            builder.append(*column, file, line);
+        }
+        self.buffered += 1;
+    }
+
+    /// Take the buffered entries as one record batch; `None` if there are none.
+    pub(crate) fn finish(&mut self) -> Result<Option<RecordBatch>, BridgeError> {
+        if self.buffered == 0 {
+            return Ok(None);
+        }
+        let arrays: Vec<ArrayRef> = self.builders.iter_mut().map(ColumnBuilder::finish).collect();
+        self.buffered = 0;
+        RecordBatch::try_new(self.schema.clone(), arrays)
+            .map(Some)
+            .map_err(|e| BridgeError::Export(format!("Failed to create record batch: {}", e)))
+    }
+}
@@ -1,44 +1,133 @@
-use std::fs::File;
 use std::path::PathBuf;
-use csv;  // Changed from csv::Writer since we use it through csv::Writer::from_writer
+use std::str::FromStr;
 use crate::error::BridgeError;
 use crate::transformer::parser::BridgeParsedAssignment;
-use crate::exporter::Exporter;
+use crate::exporter::output::Output;
+use crate::exporter::{AssignmentWriter, ColumnSchema, Compression, IfExists, StreamingExporter, TableExporter};
+use crate::analysis::MetricTable;
+
+/// When fields are wrapped in quotes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuoteStyle {
+    /// Only quote fields that contain the delimiter, quotes or newlines.
+    #[default]
+    Necessary,
+    Always,
+    NonNumeric,
+    Never,
+}
+
+impl QuoteStyle {
+    fn to_csv(self) -> csv::QuoteStyle {
+        match self {
+            QuoteStyle::Necessary => csv::QuoteStyle::Necessary,
+            QuoteStyle::Always => csv::QuoteStyle::Always,
+            QuoteStyle::NonNumeric => csv::QuoteStyle::NonNumeric,
+            QuoteStyle::Never => csv::QuoteStyle::Never,
+        }
+    }
+}
+
+impl FromStr for QuoteStyle {
+    type Err = BridgeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "necessary" => Ok(QuoteStyle::Necessary),
+            "always" => Ok(QuoteStyle::Always),
+            "non-numeric" | "non_numeric" => Ok(QuoteStyle::NonNumeric),
+            "never" => Ok(QuoteStyle::Never),
+            other => Err(BridgeError::Config(format!(
+                "Unknown quote style '{}', expected necessary, always, non-numeric or never",
+                other
+            ))),
+        }
+    }
+}
 
 pub struct CsvExporter {
     pub output_path: PathBuf,
+    pub schema: ColumnSchema,
+    pub delimiter: u8,
+    pub quote_style: QuoteStyle,
+    pub if_exists: IfExists,
+    pub compression: Compression,
+    /// Codec level; the codec's default if `None`.
+    pub compression_level: Option<i32>,
 }
 
-impl Exporter for CsvExporter {
-    fn export(&self, data: &[BridgeParsedAssignment]) -> Result<(), BridgeError> {
-        let file = File::create(&self.output_path)?;  // Now works with From implementation
-        let mut writer = csv::Writer::from_writer(file);
-
-        // Write header
-        writer.write_record(&[
-            "file_sha",
-            "published_timestamp",
-            // ... other fields ...
-        ])?;  // Now works with From implementation
-
-        for assignment in data {
-            for line in &assignment.lines {
-                writer.write_record(&[
-                    &line.sha,
-                    &line.fingerprint,
-                    &line.distribution_method,
-                    line.transport.as_deref().unwrap_or(""),
-                    line.ip.as_deref().unwrap_or(""),
-                    line.blocklist.as_deref().unwrap_or(""),
-                    &line.distributed.map_or("".to_string(), |b| b.to_string()),
-                    line.state.as_deref().unwrap_or(""),
-                    line.bandwidth.as_deref().unwrap_or(""),
-                    &line.ratio.map_or("".to_string(), |r| r.to_string()),
-                ])?;
-            }
+impl CsvExporter {
+    /// Comma-separated export of the default columns.
+    pub fn new(output_path: PathBuf) -> Self {
+        CsvExporter {
+            output_path,
+            schema: ColumnSchema::default(),
+            delimiter: b',',
+            quote_style: QuoteStyle::default(),
+            if_exists: IfExists::default(),
+            compression: Compression::default(),
+            compression_level: None,
         }
+    }
+
+    fn writer(&self) -> Result<csv::Writer<Output>, BridgeError> {
+        let file = Output::create(&self.output_path, self.if_exists, self.compression, self.compression_level)?;
+        Ok(csv::WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .quote_style(self.quote_style.to_csv())
+            .from_writer(file))
+    }
+}
+
+/// Open CSV output of a [`CsvExporter`].
+pub struct CsvWriter<'a> {
+    exporter: &'a CsvExporter,
+    writer: csv::Writer<Output>,
+}
+
+impl StreamingExporter for CsvExporter {
+    type Writer<'a> = CsvWriter<'a>;
 
-        writer.flush()?;  // Now works with From implementation
+    async fn begin(&self) -> Result<CsvWriter<'_>, BridgeError> {
+        let mut writer = self.writer()?;
+        // The header and every row are rendered from the same schema
+        writer.write_record(self.schema.header())?;
+        Ok(CsvWriter { exporter: self, writer })
+    }
+}
+
+impl AssignmentWriter for CsvWriter<'_> {
+    async fn write_assignment(&mut self, assignment: &BridgeParsedAssignment) -> Result<(), BridgeError> {
+        for line in &assignment.lines {
+            self.writer.write_record(self.exporter.schema.render_row(assignment, line))?;
+        }
+        Ok(())
+    }
+
+    async fn finish(self) -> Result<(), BridgeError> {
+        commit(self.writer)
+    }
+
+    async fn abort(self) -> Result<(), BridgeError> {
+        drop(self.writer);
         Ok(())
     }
 }
+
+impl TableExporter for CsvExporter {
+    fn export_table(&self, table: &MetricTable) -> Result<(), BridgeError> {
+        let mut writer = self.writer()?;
+
+        writer.write_record(&table.columns)?;
+        for row in &table.rows {
+            writer.write_record(row.iter().map(|v| v.to_string()))?;
+        }
+
+        commit(writer)
+    }
+}
+
+/// Flush the CSV buffer and move the file into place.
+fn commit(writer: csv::Writer<Output>) -> Result<(), BridgeError> {
+    writer.into_inner().map_err(|e| e.into_error())?.commit()
+}
@@ -0,0 +1,151 @@
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use crate::error::BridgeError;
+use crate::exporter::output::AtomicFile;
+use crate::exporter::parquet::{ParquetExporter, ParquetStreamWriter};
+use crate::exporter::IfExists;
+use crate::helper::{millis_to_utc, Digest, Sha256Digest};
+use crate::transformer::BridgeParsedAssignment;
+
+/// File at the dataset root recording which source files every part holds.
+pub const MANIFEST_FILE: &str = "_manifest.json";
+
+/// `year=YYYY/month=MM` partition -> part file name -> source `file_sha`s.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DatasetManifest {
+    pub partitions: BTreeMap<String, BTreeMap<String, Vec<String>>>,
+}
+
+impl DatasetManifest {
+    /// Read the manifest of the dataset at `root`; empty if there is none yet.
+    pub fn load(root: &Path) -> Result<Self, BridgeError> {
+        let path = root.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(DatasetManifest::default());
+        }
+        serde_json::from_str(&fs::read_to_string(&path)?)
+            .map_err(|e| BridgeError::Parse(format!("Invalid dataset manifest {}: {}", path.display(), e)))
+    }
+
+    /// Every source file already written to the dataset.
+    pub fn file_shas(&self) -> HashSet<&str> {
+        self.partitions
+            .values()
+            .flat_map(|parts| parts.values())
+            .flatten()
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Replace the manifest via a temporary file, so readers never see half of it.
+    fn save(&self, root: &Path) -> Result<(), BridgeError> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| BridgeError::Export(format!("Failed to serialise dataset manifest: {}", e)))?;
+        let mut file = AtomicFile::create(&root.join(MANIFEST_FILE), IfExists::Overwrite)?;
+        file.write_all(json.as_bytes())?;
+        file.commit()?;
+        Ok(())
+    }
+}
+
+/// Hive-style partition directory of a file published at `millis`.
+fn partition_of(millis: i64) -> Result<String, BridgeError> {
+    let published = millis_to_utc(millis).ok_or_else(|| BridgeError::InvalidTimestamp(millis.to_string()))?;
+    Ok(format!("year={:04}/month={:02}", published.year(), published.month()))
+}
+
+/// One part being written and the source files it holds.
+struct OpenPart {
+    writer: ParquetStreamWriter,
+    shas: Vec<String>,
+}
+
+/// Appends the files that are not in the manifest yet to the dataset under
+/// `exporter.output_path`, as one new part per month. Parts are written to
+/// temporary files and only named and recorded in the manifest on `finish`;
+/// existing parts are never rewritten.
+pub(crate) struct DatasetWriter<'a> {
+    exporter: &'a ParquetExporter,
+    manifest: DatasetManifest,
+    /// Files already in the dataset or written by this run.
+    written: HashSet<String>,
+    parts: BTreeMap<String, OpenPart>,
+    skipped: usize,
+}
+
+impl<'a> DatasetWriter<'a> {
+    pub(crate) fn open(exporter: &'a ParquetExporter) -> Result<Self, BridgeError> {
+        fs::create_dir_all(&exporter.output_path)?;
+        let manifest = DatasetManifest::load(&exporter.output_path)?;
+        let written = manifest.file_shas().into_iter().map(str::to_string).collect();
+        Ok(DatasetWriter { exporter, manifest, written, parts: BTreeMap::new(), skipped: 0 })
+    }
+
+    pub(crate) fn write(&mut self, file: &BridgeParsedAssignment) -> Result<(), BridgeError> {
+        if !self.written.insert(file.file_sha.clone()) {
+            self.skipped += 1;
+            return Ok(());
+        }
+        let part = match self.parts.entry(partition_of(file.published)?) {
+            Entry::Occupied(open) => open.into_mut(),
+            Entry::Vacant(new) => {
+                let dir = self.exporter.output_path.join(new.key());
+                fs::create_dir_all(&dir)?;
+                let exporter = self.exporter;
+                // Written under a temporary name and named after its contents on finish
+                let writer = ParquetExporter {
+                    output_path: dir.join("part.parquet"),
+                    schema: exporter.schema.clone(),
+                    row_group_size: exporter.row_group_size,
+                    compression: exporter.compression,
+                    bloom_filter: exporter.bloom_filter,
+                    dataset: false,
+                    source: exporter.source.clone(),
+                    if_exists: IfExists::Overwrite,
+                }
+                .writer()?;
+                new.insert(OpenPart { writer, shas: Vec::new() })
+            }
+        };
+        part.writer.write(file)?;
+        part.shas.push(file.file_sha.clone());
+        Ok(())
+    }
+
+    /// Close and name every new part, then record them in the manifest.
+    /// Returns the number of new parts.
+    pub(crate) fn finish(mut self) -> Result<usize, BridgeError> {
+        let root = &self.exporter.output_path;
+        if self.skipped > 0 {
+            info!(" Skipping {} files already in the dataset", self.skipped);
+        }
+
+        let count = self.parts.len();
+        for (partition, mut part) in self.parts {
+            part.shas.sort();
+            // Named after its contents, so a run interrupted before the manifest
+            // was saved rewrites the same part instead of adding a duplicate
+            let name = format!("part-{}.parquet", &Sha256Digest.hash_bytes(part.shas.join("\n").as_bytes())[..16]);
+            part.writer.finish_as(&root.join(&partition).join(&name))?;
+            self.manifest.partitions.entry(partition).or_default().insert(name, part.shas);
+        }
+
+        if count > 0 {
+            self.manifest.save(root)?;
+        }
+        info!(" Wrote {} new parts to dataset {}", count, root.display());
+        Ok(count)
+    }
+
+    /// Discard the unfinished parts; the manifest is left as it was.
+    pub(crate) fn abort(self) -> Result<(), BridgeError> {
+        drop(self.parts);
+        Ok(())
+    }
+}
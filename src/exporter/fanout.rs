@@ -0,0 +1,172 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+use crate::error::BridgeError;
+use crate::exporter::Exporter;
+use crate::transformer::BridgeParsedAssignment;
+
+/// What a failing output means for the outputs after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FailurePolicy {
+    /// Keep writing the remaining outputs.
+    #[default]
+    Continue,
+    /// Skip the remaining outputs.
+    Abort,
+}
+
+impl FromStr for FailurePolicy {
+    type Err = BridgeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "continue" => Ok(FailurePolicy::Continue),
+            "abort" => Ok(FailurePolicy::Abort),
+            other => Err(BridgeError::Config(format!(
+                "Unknown failure policy '{}', expected continue or abort",
+                other
+            ))),
+        }
+    }
+}
+
+/// One output of a run: a format, optionally with its own destination and
+/// `;`-separated options (`parquet=entries.parquet`, `postgres=host=db dbname=tor`,
+/// `csv=stats.csv;columns=fingerprint,method;compression=gzip`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputSpec {
+    pub format: String,
+    /// Output path, or connection string for `postgres`; the format's own
+    /// option is used when absent.
+    pub target: Option<String>,
+    /// Options of this output only, as lowercased key and value, in order.
+    pub options: Vec<(String, String)>,
+}
+
+impl FromStr for OutputSpec {
+    type Err = BridgeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || BridgeError::Config(format!("Invalid output '{}', expected FORMAT[=TARGET][;KEY=VALUE...]", s));
+        let mut parts = s.split(';');
+        let output = parts.next().unwrap_or_default();
+        let (format, target) = match output.split_once('=') {
+            Some((format, target)) => (format, Some(target.trim())),
+            None => (output, None),
+        };
+        let format = format.trim().to_ascii_lowercase();
+        if format.is_empty() || target == Some("") {
+            return Err(invalid());
+        }
+
+        let options = parts
+            .map(|option| match option.split_once('=') {
+                Some((key, value)) if !key.trim().is_empty() => {
+                    Ok((key.trim().to_ascii_lowercase(), value.trim().to_string()))
+                }
+                _ => Err(invalid()),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(OutputSpec { format, target: target.map(str::to_string), options })
+    }
+}
+
+impl fmt::Display for OutputSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.target {
+            // Connection strings may carry passwords, so only paths are shown
+            Some(ref target) if self.format != "postgres" => write!(f, "{}={}", self.format, target)?,
+            _ => f.write_str(&self.format)?,
+        }
+        for (key, value) in &self.options {
+            write!(f, ";{}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl OutputSpec {
+    /// `target` as a path, or `default` if none was given.
+    pub fn path_or(&self, default: impl Into<PathBuf>) -> PathBuf {
+        self.target.as_ref().map(PathBuf::from).unwrap_or_else(|| default.into())
+    }
+}
+
+/// How one output of a [`FanOutExporter`] run ended.
+#[derive(Debug)]
+pub enum OutputStatus {
+    Succeeded,
+    Failed(BridgeError),
+    /// Not attempted, because an earlier output failed under [`FailurePolicy::Abort`].
+    Skipped,
+}
+
+#[derive(Debug)]
+pub struct OutputReport {
+    pub name: String,
+    pub status: OutputStatus,
+    pub elapsed: Duration,
+}
+
+/// Writes the same parsed assignments to several exporters in turn.
+pub struct FanOutExporter {
+    /// Exporters with the names they are reported under, in order.
+    pub outputs: Vec<(String, Box<dyn Exporter>)>,
+    pub policy: FailurePolicy,
+}
+
+impl FanOutExporter {
+    /// Export `data` to every output, recording the outcome of each.
+    pub fn run(&self, data: &[BridgeParsedAssignment]) -> Vec<OutputReport> {
+        let mut reports = Vec::with_capacity(self.outputs.len());
+        let mut failed = false;
+
+        for (name, exporter) in &self.outputs {
+            if failed && self.policy == FailurePolicy::Abort {
+                warn!(" Output {} skipped after an earlier failure", name);
+                reports.push(OutputReport { name: name.clone(), status: OutputStatus::Skipped, elapsed: Duration::ZERO });
+                continue;
+            }
+
+            let started = Instant::now();
+            let status = match exporter.export(data) {
+                Ok(()) => {
+                    info!(" Output {} succeeded in {:.2?}", name, started.elapsed());
+                    OutputStatus::Succeeded
+                }
+                Err(e) => {
+                    error!(" Output {} failed: {}", name, e);
+                    failed = true;
+                    OutputStatus::Failed(e)
+                }
+            };
+            reports.push(OutputReport { name: name.clone(), status, elapsed: started.elapsed() });
+        }
+        reports
+    }
+}
+
+impl Exporter for FanOutExporter {
+    /// Fails, after all outputs ran (or were skipped), if any of them failed.
+    fn export(&self, data: &[BridgeParsedAssignment]) -> Result<(), BridgeError> {
+        let reports = self.run(data);
+        let failed: Vec<&str> = reports
+            .iter()
+            .filter(|r| matches!(r.status, OutputStatus::Failed(_)))
+            .map(|r| r.name.as_str())
+            .collect();
+        if failed.is_empty() {
+            return Ok(());
+        }
+        let skipped = reports.iter().filter(|r| matches!(r.status, OutputStatus::Skipped)).count();
+        Err(BridgeError::Export(format!(
+            "{} of {} outputs failed ({}){}",
+            failed.len(),
+            reports.len(),
+            failed.join(", "),
+            if skipped > 0 { format!(", {} skipped", skipped) } else { String::new() }
+        )))
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+use reqwest::{Client, ClientBuilder};
+use tracing::info;
+use crate::analysis::transport_key;
+use crate::error::BridgeError;
+use crate::exporter::output::Output;
+use crate::exporter::{AssignmentWriter, Compression, IfExists, StreamingExporter};
+use crate::transformer::BridgeParsedAssignment;
+
+/// Default number of lines sent per write request.
+pub const DEFAULT_LINES_PER_REQUEST: usize = 5_000;
+
+/// Writes per-file aggregates as InfluxDB line protocol, timestamped with
+/// the publication time in nanoseconds:
+///
+/// - `bridge_pool,method=..,transport=..,state=.. bridges=<n>i`
+/// - `bridge_ratio count=<n>i,min=..,max=..,mean=..,median=..`, for files with ratios
+pub struct InfluxExporter {
+    /// Output file, or `-` for stdout; `None` to only send to `write_url`.
+    pub output_path: Option<PathBuf>,
+    /// Write endpoint the lines are POSTed to, e.g.
+    /// `http://localhost:8086/api/v2/write?org=tor&bucket=bridges` or `http://localhost:8086/write?db=tor`.
+    pub write_url: Option<String>,
+    /// Sent as `Authorization: Token <token>`.
+    pub token: Option<String>,
+    /// Lines per write request.
+    pub batch_size: usize,
+    pub if_exists: IfExists,
+    pub compression: Compression,
+    /// Codec level; the codec's default if `None`.
+    pub compression_level: Option<i32>,
+}
+
+impl InfluxExporter {
+    pub fn new(output_path: Option<PathBuf>) -> Self {
+        InfluxExporter {
+            output_path,
+            write_url: None,
+            token: None,
+            batch_size: DEFAULT_LINES_PER_REQUEST,
+            if_exists: IfExists::default(),
+            compression: Compression::default(),
+            compression_level: None,
+        }
+    }
+}
+
+/// Escape a tag value: commas, equals signs and spaces are significant.
+fn escape_tag(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(',', "\\,").replace('=', "\\=").replace(' ', "\\ ")
+}
+
+/// Middle value of `sorted`, or the mean of the two middle ones.
+fn median(sorted: &[f32]) -> f32 {
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+/// The line-protocol points of one file.
+fn points(file: &BridgeParsedAssignment) -> Vec<String> {
+    let timestamp = file.published * 1_000_000;
+    let mut counts: BTreeMap<(&str, String, &str), u64> = BTreeMap::new();
+    let mut ratios: Vec<f32> = Vec::new();
+    for line in &file.lines {
+        let key = (
+            line.distribution_method.as_str(),
+            transport_key(line.transport.as_deref()),
+            line.state.as_deref().unwrap_or("unknown"),
+        );
+        *counts.entry(key).or_default() += 1;
+        // `ratio=NaN` or `ratio=inf` parse, but are not valid line protocol
+        ratios.extend(line.ratio.filter(|r| r.is_finite()));
+    }
+
+    let mut points: Vec<String> = counts
+        .into_iter()
+        .map(|((method, transport, state), bridges)| {
+            format!(
+                "bridge_pool,method={},transport={},state={} bridges={}i {}",
+                escape_tag(method),
+                escape_tag(&transport),
+                escape_tag(state),
+                bridges,
+                timestamp
+            )
+        })
+        .collect();
+
+    if !ratios.is_empty() {
+        ratios.sort_by(f32::total_cmp);
+        let mean = ratios.iter().map(|&r| r as f64).sum::<f64>() / ratios.len() as f64;
+        points.push(format!(
+            "bridge_ratio count={}i,min={},max={},mean={},median={} {}",
+            ratios.len(),
+            ratios[0],
+            ratios[ratios.len() - 1],
+            mean,
+            median(&ratios),
+            timestamp
+        ));
+    }
+    points
+}
+
+/// Open output of an [`InfluxExporter`]. Lines are sent every `batch_size`
+/// lines; an abort discards the file, but cannot take back requests already sent.
+pub struct InfluxWriter<'a> {
+    exporter: &'a InfluxExporter,
+    out: Option<Output>,
+    client: Option<Client>,
+    /// Lines not sent yet.
+    pending: String,
+    pending_lines: usize,
+    lines: usize,
+}
+
+impl StreamingExporter for InfluxExporter {
+    type Writer<'a> = InfluxWriter<'a>;
+
+    async fn begin(&self) -> Result<InfluxWriter<'_>, BridgeError> {
+        if self.output_path.is_none() && self.write_url.is_none() {
+            return Err(BridgeError::Config("InfluxDB export needs an output path or a write URL".into()));
+        }
+        let out = match self.output_path {
+            Some(ref path) => Some(Output::create(path, self.if_exists, self.compression, self.compression_level)?),
+            None => None,
+        };
+        let client = match self.write_url {
+            Some(_) => Some(
+                ClientBuilder::new()
+                    .timeout(Duration::from_secs(30))
+                    .build()
+                    .map_err(|e| BridgeError::Fetch(e.to_string()))?,
+            ),
+            None => None,
+        };
+        Ok(InfluxWriter { exporter: self, out, client, pending: String::new(), pending_lines: 0, lines: 0 })
+    }
+}
+
+impl InfluxWriter<'_> {
+    /// POST the pending lines to the write endpoint.
+    async fn send(&mut self) -> Result<(), BridgeError> {
+        let (client, url) = match (&self.client, &self.exporter.write_url) {
+            (Some(client), Some(url)) if self.pending_lines > 0 => (client, url),
+            _ => return Ok(()),
+        };
+        let mut request = client
+            .post(url)
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .body(std::mem::take(&mut self.pending));
+        if let Some(ref token) = self.exporter.token {
+            request = request.header("Authorization", format!("Token {}", token));
+        }
+        let response = request.send().await.map_err(|e| BridgeError::Fetch(e.to_string()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(BridgeError::HttpError(format!("InfluxDB write failed: {} {}", status, body.trim())));
+        }
+        self.pending_lines = 0;
+        Ok(())
+    }
+}
+
+impl AssignmentWriter for InfluxWriter<'_> {
+    async fn write_assignment(&mut self, assignment: &BridgeParsedAssignment) -> Result<(), BridgeError> {
+        for point in points(assignment) {
+            if let Some(ref mut out) = self.out {
+                writeln!(out, "{}", point)?;
+            }
+            if self.client.is_some() {
+                let _ = writeln!(self.pending, "{}", point);
+                self.pending_lines += 1;
+                if self.pending_lines >= self.exporter.batch_size.max(1) {
+                    self.send().await?;
+                }
+            }
+            self.lines += 1;
+        }
+        Ok(())
+    }
+
+    async fn finish(mut self) -> Result<(), BridgeError> {
+        self.send().await?;
+        if let Some(out) = self.out {
+            out.commit()?;
+        }
+        info!("✅ Exported {} InfluxDB lines", self.lines);
+        Ok(())
+    }
+
+    async fn abort(self) -> Result<(), BridgeError> {
+        drop(self.out);
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use arrow::error::ArrowError;
+use arrow::ipc::writer::{FileWriter, StreamWriter};
+use arrow::record_batch::RecordBatch;
+use tracing::info;
+use crate::error::BridgeError;
+use crate::exporter::batch::{self, BatchBuilder};
+use crate::exporter::output::Output;
+use crate::exporter::schema::ColumnSchema;
+use crate::exporter::{AssignmentWriter, Compression, IfExists, StreamingExporter};
+use crate::transformer::BridgeParsedAssignment;
+
+/// Default number of entries per Arrow record batch.
+pub const DEFAULT_BATCH_SIZE: usize = 65_536;
+
+/// Arrow IPC container format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IpcFormat {
+    /// Random-access file with a footer (`.arrow`), for memory-mapping.
+    #[default]
+    File,
+    /// Sequence of messages without a footer (`.arrows`), readable while it is written.
+    Stream,
+}
+
+impl FromStr for IpcFormat {
+    type Err = BridgeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "file" => Ok(IpcFormat::File),
+            "stream" => Ok(IpcFormat::Stream),
+            other => Err(BridgeError::Config(format!(
+                "Unknown Arrow IPC format '{}', expected file or stream",
+                other
+            ))),
+        }
+    }
+}
+
+/// Writes entries as Arrow IPC, with the same columns and types as the Parquet export.
+pub struct ArrowIpcExporter {
+    /// Output file, or `-` for stdout.
+    pub output_path: PathBuf,
+    pub schema: ColumnSchema,
+    pub format: IpcFormat,
+    /// Entries per record batch.
+    pub batch_size: usize,
+    /// Where the assignments came from, recorded in the schema metadata.
+    pub source: Option<String>,
+    pub if_exists: IfExists,
+}
+
+impl ArrowIpcExporter {
+    /// File export of the default columns.
+    pub fn new(output_path: PathBuf) -> Self {
+        ArrowIpcExporter {
+            output_path,
+            schema: ColumnSchema::default(),
+            format: IpcFormat::default(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            source: None,
+            if_exists: IfExists::default(),
+        }
+    }
+
+    /// Open `output_path` for writing assignments one file at a time.
+    ///
+    /// Categorical columns are dictionary-encoded in streams only: the file
+    /// format allows a single dictionary per column, which batches built one
+    /// after another cannot share.
+    pub fn writer(&self) -> Result<ArrowIpcWriter, BridgeError> {
+        let batch = BatchBuilder::new(
+            &self.schema,
+            batch::metadata(self.source.as_deref()),
+            self.format == IpcFormat::Stream,
+        );
+        let schema = batch.schema();
+
+        info!(" Creating Arrow IPC {:?}: {}", self.format, self.output_path.display());
+        let out = Output::create(&self.output_path, self.if_exists, Compression::None, None)?;
+        let writer = match self.format {
+            IpcFormat::File => IpcWriter::File(FileWriter::try_new(out, &schema).map_err(ipc_error)?),
+            IpcFormat::Stream => IpcWriter::Stream(StreamWriter::try_new(out, &schema).map_err(ipc_error)?),
+        };
+
+        Ok(ArrowIpcWriter {
+            writer,
+            batch,
+            batch_size: self.batch_size.max(1),
+            rows: 0,
+        })
+    }
+}
+
+fn ipc_error(e: ArrowError) -> BridgeError {
+    BridgeError::Export(format!("Arrow IPC write failed: {}", e))
+}
+
+enum IpcWriter {
+    File(FileWriter<Output>),
+    Stream(StreamWriter<Output>),
+}
+
+/// Incremental Arrow IPC writer from [`ArrowIpcExporter::writer`]; every
+/// full batch is written out immediately, so a stream can be consumed as it
+/// is produced.
+pub struct ArrowIpcWriter {
+    writer: IpcWriter,
+    batch: BatchBuilder,
+    batch_size: usize,
+    rows: usize,
+}
+
+impl ArrowIpcWriter {
+    /// Append the entries of one file, writing every batch that fills up.
+    pub fn write(&mut self, file: &BridgeParsedAssignment) -> Result<(), BridgeError> {
+        for line in &file.lines {
+            self.batch.append(file, line);
+            if self.batch.len() == self.batch_size {
+                self.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), BridgeError> {
+        let batch: RecordBatch = match self.batch.finish()? {
+            Some(batch) => batch,
+            None => return Ok(()),
+        };
+        match self.writer {
+            IpcWriter::File(ref mut w) => w.write(&batch),
+            IpcWriter::Stream(ref mut w) => w.write(&batch),
+        }
+        .map_err(ipc_error)?;
+        self.rows += batch.num_rows();
+        Ok(())
+    }
+
+    /// Write the last batch and the end-of-stream marker or file footer,
+    /// then move the file into place; returns the number of entries.
+    fn close(mut self) -> Result<usize, BridgeError> {
+        self.flush()?;
+        match self.writer {
+            IpcWriter::File(w) => w.into_inner(),
+            IpcWriter::Stream(w) => w.into_inner(),
+        }
+        .map_err(ipc_error)?
+        .commit()?;
+        Ok(self.rows)
+    }
+}
+
+impl StreamingExporter for ArrowIpcExporter {
+    type Writer<'a> = ArrowIpcWriter;
+
+    async fn begin(&self) -> Result<ArrowIpcWriter, BridgeError> {
+        self.writer()
+    }
+}
+
+impl AssignmentWriter for ArrowIpcWriter {
+    async fn write_assignment(&mut self, assignment: &BridgeParsedAssignment) -> Result<(), BridgeError> {
+        self.write(assignment)
+    }
+
+    async fn finish(self) -> Result<(), BridgeError> {
+        let rows = self.close()?;
+        info!("✅ Successfully exported {} entries to Arrow IPC format", rows);
+        Ok(())
+    }
+
+    async fn abort(self) -> Result<(), BridgeError> {
+        drop(self.writer);
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+use std::io::Write;
+use std::path::PathBuf;
+use serde::Serialize;
+use crate::error::BridgeError;
+use crate::transformer::{BridgeLineEntry, BridgeParsedAssignment};
+use crate::exporter::output::Output;
+use crate::exporter::{AssignmentWriter, Compression, IfExists, StreamingExporter};
+
+/// Writes all assignments as one JSON array of files, each with its nested entries.
+pub struct JsonExporter {
+    /// Output file, or `-` for stdout.
+    pub output_path: PathBuf,
+    pub pretty: bool,
+    pub if_exists: IfExists,
+    pub compression: Compression,
+    /// Codec level; the codec's default if `None`.
+    pub compression_level: Option<i32>,
+}
+
+impl JsonExporter {
+    /// Compact JSON export.
+    pub fn new(output_path: PathBuf) -> Self {
+        JsonExporter {
+            output_path,
+            pretty: false,
+            if_exists: IfExists::default(),
+            compression: Compression::default(),
+            compression_level: None,
+        }
+    }
+}
+
+/// Writes one flattened JSON object per entry and line (newline-delimited JSON).
+///
+/// Every line carries the `file_sha` and `published` time of its file. Output
+/// is always compact, since a pretty-printed record would span several lines.
+pub struct NdjsonExporter {
+    /// Output file, or `-` for stdout.
+    pub output_path: PathBuf,
+    pub if_exists: IfExists,
+    pub compression: Compression,
+    /// Codec level; the codec's default if `None`.
+    pub compression_level: Option<i32>,
+}
+
+impl NdjsonExporter {
+    pub fn new(output_path: PathBuf) -> Self {
+        NdjsonExporter {
+            output_path,
+            if_exists: IfExists::default(),
+            compression: Compression::default(),
+            compression_level: None,
+        }
+    }
+}
+
+/// One NDJSON record: an entry plus the file-level fields it belongs to.
+#[derive(Serialize)]
+struct FlatEntry<'a> {
+    file_sha: &'a str,
+    published: i64,
+    #[serde(flatten)]
+    entry: &'a BridgeLineEntry,
+}
+
+fn json_error(e: serde_json::Error) -> BridgeError {
+    BridgeError::Export(format!("JSON serialization failed: {}", e))
+}
+
+/// Open output of a [`JsonExporter`]. Files are written as they arrive,
+/// as elements of the top-level array.
+pub struct JsonWriter<'a> {
+    exporter: &'a JsonExporter,
+    out: Output,
+    files: usize,
+}
+
+impl StreamingExporter for JsonExporter {
+    type Writer<'a> = JsonWriter<'a>;
+
+    async fn begin(&self) -> Result<JsonWriter<'_>, BridgeError> {
+        let mut out = Output::create(&self.output_path, self.if_exists, self.compression, self.compression_level)?;
+        out.write_all(b"[")?;
+        Ok(JsonWriter { exporter: self, out, files: 0 })
+    }
+}
+
+impl AssignmentWriter for JsonWriter<'_> {
+    async fn write_assignment(&mut self, assignment: &BridgeParsedAssignment) -> Result<(), BridgeError> {
+        if self.files > 0 {
+            self.out.write_all(b",")?;
+        }
+        if self.exporter.pretty {
+            // Same layout as pretty-printing the whole array: one element per
+            // level of indentation, nested lines shifted along with it
+            let element = serde_json::to_string_pretty(assignment).map_err(json_error)?;
+            write!(self.out, "\n  {}", element.replace('\n', "\n  "))?;
+        } else {
+            serde_json::to_writer(&mut self.out, assignment).map_err(json_error)?;
+        }
+        self.files += 1;
+        Ok(())
+    }
+
+    async fn finish(mut self) -> Result<(), BridgeError> {
+        if self.exporter.pretty && self.files > 0 {
+            self.out.write_all(b"\n")?;
+        }
+        self.out.write_all(b"]\n")?;
+        self.out.commit()
+    }
+
+    async fn abort(self) -> Result<(), BridgeError> {
+        drop(self.out);
+        Ok(())
+    }
+}
+
+/// Open output of an [`NdjsonExporter`].
+pub struct NdjsonWriter {
+    out: Output,
+}
+
+impl StreamingExporter for NdjsonExporter {
+    type Writer<'a> = NdjsonWriter;
+
+    async fn begin(&self) -> Result<NdjsonWriter, BridgeError> {
+        let out = Output::create(&self.output_path, self.if_exists, self.compression, self.compression_level)?;
+        Ok(NdjsonWriter { out })
+    }
+}
+
+impl AssignmentWriter for NdjsonWriter {
+    async fn write_assignment(&mut self, assignment: &BridgeParsedAssignment) -> Result<(), BridgeError> {
+        for entry in &assignment.lines {
+            let record = FlatEntry {
+                file_sha: &assignment.file_sha,
+                published: assignment.published,
+                entry,
+            };
+            serde_json::to_writer(&mut self.out, &record).map_err(json_error)?;
+            writeln!(self.out)?;
+        }
+        Ok(())
+    }
+
+    async fn finish(self) -> Result<(), BridgeError> {
+        self.out.commit()
+    }
+
+    async fn abort(self) -> Result<(), BridgeError> {
+        drop(self.out);
+        Ok(())
+    }
+}
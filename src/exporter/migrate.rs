@@ -0,0 +1,156 @@
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use tokio_postgres::Client;
+use tracing::info;
+use crate::error::BridgeError;
+
+/// One schema change, embedded into the binary at build time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// All migrations, in the order they are applied. Never edit a released
+/// migration; append a new one instead.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        sql: include_str!("migrations/0001_initial.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "lookup_indexes",
+        sql: include_str!("migrations/0002_lookup_indexes.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "normalized_layout",
+        sql: include_str!("migrations/0003_normalized_layout.sql"),
+    },
+];
+
+/// Schema version this binary was built for.
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+/// Whether a known migration has been applied, and when.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: String,
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+/// Arbitrary key for the advisory lock that serialises concurrent migrators.
+const MIGRATION_LOCK_KEY: i64 = 0x6272_6964_6765;
+
+/// Create `schema_migrations` if needed and lock it for the rest of the transaction.
+async fn prepare(tx: &Client) -> Result<(), BridgeError> {
+    tx.batch_execute(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version BIGINT PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        )",
+    )
+    .await
+    .map_err(|e| BridgeError::Database(format!("Creating schema_migrations failed: {}", e)))?;
+
+    tx.execute("SELECT pg_advisory_xact_lock($1)", &[&MIGRATION_LOCK_KEY])
+        .await
+        .map_err(|e| BridgeError::Database(format!("Locking schema_migrations failed: {}", e)))?;
+
+    Ok(())
+}
+
+/// Applied versions with their names and timestamps.
+async fn applied(tx: &Client) -> Result<BTreeMap<i64, (String, DateTime<Utc>)>, BridgeError> {
+    let rows = tx
+        .query("SELECT version, name, applied_at FROM schema_migrations", &[])
+        .await
+        .map_err(|e| BridgeError::Database(format!("Reading schema_migrations failed: {}", e)))?;
+
+    Ok(rows
+        .iter()
+        .map(|row| (row.get(0), (row.get(1), row.get(2))))
+        .collect())
+}
+
+/// Fail if the database has been migrated by a newer binary.
+fn check_not_newer(applied: &BTreeMap<i64, (String, DateTime<Utc>)>) -> Result<(), BridgeError> {
+    match applied.keys().next_back() {
+        Some(&version) if version > latest_version() => Err(BridgeError::Database(format!(
+            "Database schema is at version {} but this binary only knows up to version {}; upgrade bridge-parser",
+            version,
+            latest_version()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Apply every pending migration inside `tx` and return the ones applied.
+///
+/// With `dry_run` the pending migrations are only returned; the caller is
+/// expected to roll the transaction back.
+pub async fn migrate_up(tx: &Client, dry_run: bool) -> Result<Vec<Migration>, BridgeError> {
+    prepare(tx).await?;
+    let applied = applied(tx).await?;
+    check_not_newer(&applied)?;
+
+    let pending: Vec<Migration> = MIGRATIONS
+        .iter()
+        .filter(|m| !applied.contains_key(&m.version))
+        .copied()
+        .collect();
+
+    if dry_run {
+        return Ok(pending);
+    }
+
+    for migration in &pending {
+        tx.batch_execute(migration.sql).await.map_err(|e| {
+            BridgeError::Database(format!(
+                "Migration {} ({}) failed: {}",
+                migration.version, migration.name, e
+            ))
+        })?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name) VALUES ($1, $2)",
+            &[&migration.version, &migration.name],
+        )
+        .await
+        .map_err(|e| BridgeError::Database(format!("Recording migration {} failed: {}", migration.version, e)))?;
+        info!(version = migration.version, "Applied migration '{}'", migration.name);
+    }
+
+    Ok(pending)
+}
+
+/// Status of every migration known to this binary or recorded in the database.
+pub async fn migration_status(tx: &Client) -> Result<Vec<MigrationStatus>, BridgeError> {
+    prepare(tx).await?;
+    let mut applied = applied(tx).await?;
+
+    let mut status: Vec<MigrationStatus> = MIGRATIONS
+        .iter()
+        .map(|m| MigrationStatus {
+            version: m.version,
+            name: m.name.to_string(),
+            applied_at: applied.remove(&m.version).map(|(_, at)| at),
+        })
+        .collect();
+
+    // Versions applied by a newer binary
+    status.extend(applied.into_iter().map(|(version, (name, at))| MigrationStatus {
+        version,
+        name,
+        applied_at: Some(at),
+    }));
+    status.sort_by_key(|s| s.version);
+
+    Ok(status)
+}
@@ -0,0 +1,22 @@
+-- Flat layout used since the first release. IF NOT EXISTS lets databases
+-- created before migrations existed adopt this version unchanged.
+CREATE TABLE IF NOT EXISTS bridge_file (
+    sha TEXT PRIMARY KEY,
+    header TEXT NOT NULL,
+    published TIMESTAMP NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS bridge_entry (
+    sha TEXT PRIMARY KEY,
+    fingerprint TEXT NOT NULL,
+    method TEXT NOT NULL,
+    file_sha TEXT REFERENCES bridge_file(sha),
+    transport TEXT,
+    ip TEXT,
+    block TEXT,
+    distributed BOOLEAN,
+    state TEXT,
+    bandwidth TEXT,
+    ratio REAL,
+    published TIMESTAMP NOT NULL
+);
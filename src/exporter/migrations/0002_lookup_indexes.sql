@@ -0,0 +1,4 @@
+-- Per-bridge and time-range lookups, matching the SQLite export.
+CREATE INDEX IF NOT EXISTS bridge_entry_fingerprint_idx ON bridge_entry (fingerprint);
+CREATE INDEX IF NOT EXISTS bridge_entry_published_idx ON bridge_entry (published);
+CREATE INDEX IF NOT EXISTS bridge_file_published_idx ON bridge_file (published);
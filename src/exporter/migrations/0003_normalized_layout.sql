@@ -0,0 +1,85 @@
+-- Optional normalised layout, filled by --pg-layout normalized. It lives in its
+-- own schema so the views below can keep the flat table names: with
+-- `SET search_path = bridge_norm, public` existing queries run unchanged.
+CREATE SCHEMA IF NOT EXISTS bridge_norm;
+
+CREATE TABLE bridge_norm.distribution_method (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE bridge_norm.transport (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE bridge_norm.state (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE bridge_norm.bandwidth (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE bridge_norm.file (
+    sha TEXT PRIMARY KEY,
+    header TEXT NOT NULL,
+    published TIMESTAMPTZ NOT NULL
+);
+
+-- One row per bridge, with the first and last snapshot it appeared in.
+CREATE TABLE bridge_norm.bridge (
+    id BIGSERIAL PRIMARY KEY,
+    fingerprint TEXT NOT NULL UNIQUE,
+    first_seen TIMESTAMPTZ NOT NULL,
+    last_seen TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE bridge_norm.assignment (
+    sha TEXT PRIMARY KEY,
+    file_sha TEXT NOT NULL REFERENCES bridge_norm.file(sha),
+    bridge_id BIGINT NOT NULL REFERENCES bridge_norm.bridge(id),
+    method_id INTEGER NOT NULL REFERENCES bridge_norm.distribution_method(id),
+    transport_id INTEGER REFERENCES bridge_norm.transport(id),
+    -- IP versions the bridge is reachable on, e.g. {4,6}
+    ip_versions SMALLINT[],
+    -- Validated lowercase ISO 3166-1 alpha-2 codes
+    blocklist TEXT[],
+    distributed BOOLEAN,
+    state_id INTEGER REFERENCES bridge_norm.state(id),
+    bandwidth_id INTEGER REFERENCES bridge_norm.bandwidth(id),
+    ratio REAL,
+    published TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX assignment_bridge_published_idx ON bridge_norm.assignment (bridge_id, published);
+CREATE INDEX assignment_file_sha_idx ON bridge_norm.assignment (file_sha);
+CREATE INDEX assignment_published_idx ON bridge_norm.assignment (published);
+
+-- Backward-compatible views in the shape of the flat tables
+CREATE VIEW bridge_norm.bridge_file AS
+SELECT sha, header, published AT TIME ZONE 'UTC' AS published
+FROM bridge_norm.file;
+
+CREATE VIEW bridge_norm.bridge_entry AS
+SELECT
+    a.sha,
+    b.fingerprint,
+    m.name AS method,
+    a.file_sha,
+    t.name AS transport,
+    array_to_string(a.ip_versions, ',') AS ip,
+    array_to_string(a.blocklist, ',') AS block,
+    a.distributed,
+    s.name AS state,
+    bw.name AS bandwidth,
+    a.ratio,
+    a.published AT TIME ZONE 'UTC' AS published
+FROM bridge_norm.assignment a
+JOIN bridge_norm.bridge b ON b.id = a.bridge_id
+JOIN bridge_norm.distribution_method m ON m.id = a.method_id
+LEFT JOIN bridge_norm.transport t ON t.id = a.transport_id
+LEFT JOIN bridge_norm.state s ON s.id = a.state_id
+LEFT JOIN bridge_norm.bandwidth bw ON bw.id = a.bandwidth_id;
@@ -1,16 +1,145 @@
 use crate::transformer::BridgeParsedAssignment;
+use crate::analysis::MetricTable;
 use crate::error::BridgeError;
+use std::future::Future;
+use tokio::runtime::{Handle, Runtime, RuntimeFlavor};
+use tracing::warn;
 
 mod pg;
+mod pg_norm;
+mod pg_views;
+mod partition;
+pub mod migrate;
+mod tls;
 mod csv;
+mod json;
+mod output;
+mod fanout;
+mod prometheus;
+mod influx;
+pub mod schema;
+#[cfg(any(feature = "parquet_export", feature = "arrow_export"))]
+mod batch;
 #[cfg(feature = "parquet_export")]
 mod parquet;
+#[cfg(feature = "parquet_export")]
+mod dataset;
+#[cfg(feature = "arrow_export")]
+mod ipc;
+#[cfg(feature = "sqlite_export")]
+mod sqlite;
 
-pub use pg::PostgresExporter;
-pub use csv::CsvExporter;
+pub use pg::{ExportMode, Layout, LoadMode, LoadStats, PostgresExporter, PostgresWriter};
+pub use migrate::{Migration, MigrationStatus};
+pub use partition::{OlderThan, Partition, PruneAction};
+pub use pg_views::SummaryView;
+pub use tls::{SslMode, TlsConfig};
+pub use csv::{CsvExporter, CsvWriter, QuoteStyle};
+pub use json::{JsonExporter, JsonWriter, NdjsonExporter, NdjsonWriter};
+pub use output::{Compression, IfExists};
+pub(crate) use output::AtomicFile;
+pub use fanout::{FailurePolicy, FanOutExporter, OutputReport, OutputSpec, OutputStatus};
+pub use prometheus::{PrometheusExporter, PrometheusWriter};
+pub use influx::{InfluxExporter, InfluxWriter, DEFAULT_LINES_PER_REQUEST};
+pub use schema::{Column, ColumnSchema, TimestampFormat};
+#[cfg(feature = "parquet_export")]
+pub use dataset::{DatasetManifest, MANIFEST_FILE};
 #[cfg(feature = "parquet_export")]
-pub use parquet::ParquetExporter;
+pub use parquet::{ParquetCompression, ParquetExporter, ParquetStreamWriter, ParquetWriter, DEFAULT_ROW_GROUP_SIZE};
+#[cfg(feature = "arrow_export")]
+pub use ipc::{ArrowIpcExporter, ArrowIpcWriter, IpcFormat, DEFAULT_BATCH_SIZE};
+#[cfg(feature = "sqlite_export")]
+pub use sqlite::{SqliteExporter, SqliteWriter};
 
+/// Synchronous, whole-batch export. Every [`StreamingExporter`] is also an
+/// `Exporter`, by streaming `data` through it on a blocking runtime.
 pub trait Exporter {
     fn export(&self, data: &[BridgeParsedAssignment]) -> Result<(), BridgeError>;
 }
+
+/// Async exporter that receives assignments one file at a time, for use
+/// from within an existing Tokio runtime.
+///
+/// `begin` opens the output; the returned writer takes every assignment and
+/// is then either finished, which makes the output complete, or aborted,
+/// which discards what it can.
+pub trait StreamingExporter {
+    type Writer<'a>: AssignmentWriter + Send
+    where
+        Self: 'a;
+
+    fn begin(&self) -> impl Future<Output = Result<Self::Writer<'_>, BridgeError>> + Send;
+}
+
+/// One open output of a [`StreamingExporter`].
+pub trait AssignmentWriter {
+    fn write_assignment(
+        &mut self,
+        assignment: &BridgeParsedAssignment,
+    ) -> impl Future<Output = Result<(), BridgeError>> + Send;
+
+    fn finish(self) -> impl Future<Output = Result<(), BridgeError>> + Send;
+
+    fn abort(self) -> impl Future<Output = Result<(), BridgeError>> + Send;
+}
+
+/// Stream `data` through `exporter`, aborting the output if a write fails.
+pub async fn export_stream<E: StreamingExporter + Sync>(
+    exporter: &E,
+    data: &[BridgeParsedAssignment],
+) -> Result<(), BridgeError> {
+    let mut writer = exporter.begin().await?;
+    for assignment in data {
+        if let Err(e) = writer.write_assignment(assignment).await {
+            abort_after(writer, &e).await;
+            return Err(e);
+        }
+    }
+    writer.finish().await
+}
+
+/// Abort `writer` after `cause`; a failing abort is only logged, since the
+/// original error is the one worth returning.
+pub(crate) async fn abort_after<W: AssignmentWriter>(writer: W, cause: &BridgeError) {
+    if let Err(e) = writer.abort().await {
+        warn!("Aborting the export after '{}' failed too: {}", cause, e);
+    }
+}
+
+impl<E: StreamingExporter + Sync> Exporter for E {
+    fn export(&self, data: &[BridgeParsedAssignment]) -> Result<(), BridgeError> {
+        block_on(export_stream(self, data))
+    }
+}
+
+/// Run `fut` to completion from synchronous code.
+///
+/// Outside a runtime a private one is started. Inside a multi-threaded
+/// runtime the current worker blocks in place; a current-thread runtime
+/// cannot be blocked, so `fut` runs on a private runtime in a helper thread.
+pub(crate) fn block_on<T: Send>(fut: impl Future<Output = Result<T, BridgeError>> + Send) -> Result<T, BridgeError> {
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(|| handle.block_on(fut))
+        }
+        Ok(_) => std::thread::scope(|scope| {
+            scope
+                .spawn(|| private_runtime()?.block_on(fut))
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        }),
+        Err(_) => private_runtime()?.block_on(fut),
+    }
+}
+
+fn private_runtime() -> Result<Runtime, BridgeError> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| BridgeError::Export(format!("Tokio runtime init failed: {}", e)))
+}
+
+/// Exporters that can also write analysis result tables.
+pub trait TableExporter {
+    fn export_table(&self, table: &MetricTable) -> Result<(), BridgeError>;
+}
@@ -0,0 +1,355 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Stdout, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use flate2::write::GzEncoder;
+use tracing::info;
+use xz2::write::XzEncoder;
+use crate::error::BridgeError;
+
+/// What a file export does when its output already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IfExists {
+    /// Replace the existing file (SQLite: add to the existing database).
+    #[default]
+    Overwrite,
+    /// Fail instead of touching the existing file.
+    Refuse,
+    /// Keep the existing file and write `name.1.ext`, `name.2.ext`, ... instead.
+    Version,
+}
+
+impl FromStr for IfExists {
+    type Err = BridgeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "overwrite" => Ok(IfExists::Overwrite),
+            "refuse" => Ok(IfExists::Refuse),
+            "version" => Ok(IfExists::Version),
+            other => Err(BridgeError::Config(format!(
+                "Unknown if-exists policy '{}', expected overwrite, refuse or version",
+                other
+            ))),
+        }
+    }
+}
+
+/// Compression of a text output (CSV, JSON, NDJSON).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    /// By the output's extension: `.gz`, `.xz`, `.zst`, otherwise none.
+    #[default]
+    Auto,
+    None,
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+impl FromStr for Compression {
+    type Err = BridgeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "auto" => Ok(Compression::Auto),
+            "none" => Ok(Compression::None),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "xz" => Ok(Compression::Xz),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            other => Err(BridgeError::Config(format!(
+                "Unknown compression '{}', expected auto, none, gzip, xz or zstd",
+                other
+            ))),
+        }
+    }
+}
+
+impl Compression {
+    /// Codec of a file name ending in a compression extension.
+    fn from_extension(path: &Path) -> Compression {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("xz") => Compression::Xz,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// The codec to write `path` with.
+    pub fn resolve(self, path: &Path) -> Compression {
+        match self {
+            Compression::Auto => Compression::from_extension(path),
+            codec => codec,
+        }
+    }
+}
+
+/// `path` with `.n` inserted before its extension, and before a compression
+/// extension too (`entries.1.csv.gz`); `n == 0` is `path` itself.
+fn versioned(path: &Path, n: usize) -> PathBuf {
+    if n == 0 {
+        return path.to_path_buf();
+    }
+    let mut stem = PathBuf::from(path.file_name().unwrap_or_default());
+    let mut suffix = String::new();
+    let codec = Compression::from_extension(&stem);
+    for _ in 0..if codec == Compression::None { 1 } else { 2 } {
+        if let Some(ext) = stem.extension() {
+            suffix = format!(".{}{}", ext.to_string_lossy(), suffix);
+            stem = PathBuf::from(stem.file_stem().unwrap_or_default());
+        }
+    }
+    path.with_file_name(format!("{}.{}{}", stem.display(), n, suffix))
+}
+
+fn already_exists(path: &Path) -> BridgeError {
+    BridgeError::Export(format!("Output {} already exists", path.display()))
+}
+
+/// A temporary path next to an output, moved onto it by [`PendingPath::commit`].
+/// Dropping it uncommitted removes the temporary file, so a failed export
+/// never leaves a truncated file under the output's name.
+pub(crate) struct PendingPath {
+    tmp: PathBuf,
+    path: PathBuf,
+    if_exists: IfExists,
+    committed: bool,
+}
+
+impl PendingPath {
+    pub(crate) fn new(path: &Path, if_exists: IfExists) -> Result<Self, BridgeError> {
+        if if_exists == IfExists::Refuse && path.exists() {
+            return Err(already_exists(path));
+        }
+        // Same directory, so the final rename cannot cross file systems
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let tmp = path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+        Ok(PendingPath { tmp, path: path.to_path_buf(), if_exists, committed: false })
+    }
+
+    pub(crate) fn tmp(&self) -> &Path {
+        &self.tmp
+    }
+
+    /// Flush the temporary file to disk and move it into place; returns the
+    /// path it ended up under, which differs from the output for
+    /// [`IfExists::Version`] when the output already exists.
+    pub(crate) fn commit(mut self) -> Result<PathBuf, BridgeError> {
+        File::open(&self.tmp)?.sync_all()?;
+        let placed = match self.if_exists {
+            IfExists::Overwrite => {
+                fs::rename(&self.tmp, &self.path)?;
+                self.path.clone()
+            }
+            IfExists::Refuse => {
+                place_new(&self.tmp, &self.path)?;
+                self.path.clone()
+            }
+            IfExists::Version => {
+                let mut n = 0;
+                loop {
+                    let candidate = versioned(&self.path, n);
+                    match place_new(&self.tmp, &candidate) {
+                        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => n += 1,
+                        result => break result.map(|_| candidate)?,
+                    }
+                }
+            }
+        };
+        self.committed = true;
+        sync_dir(&placed)?;
+
+        if placed != self.path {
+            info!(" {} already exists, wrote {} instead", self.path.display(), placed.display());
+        }
+        Ok(placed)
+    }
+}
+
+impl Drop for PendingPath {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.tmp);
+        }
+    }
+}
+
+/// Move `tmp` to `path` unless `path` exists. A hard link fails atomically
+/// if it does; file systems without hard links fall back to check-and-rename.
+fn place_new(tmp: &Path, path: &Path) -> io::Result<()> {
+    match fs::hard_link(tmp, path) {
+        Ok(()) => fs::remove_file(tmp),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Err(e),
+        Err(_) if path.exists() => Err(io::ErrorKind::AlreadyExists.into()),
+        Err(_) => fs::rename(tmp, path),
+    }
+}
+
+/// Persist the directory entry of a renamed file.
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => File::open(dir)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Buffered file written under a temporary name; see [`PendingPath`].
+pub(crate) struct AtomicFile {
+    file: BufWriter<File>,
+    pending: PendingPath,
+}
+
+impl AtomicFile {
+    pub(crate) fn create(path: &Path, if_exists: IfExists) -> Result<Self, BridgeError> {
+        let pending = PendingPath::new(path, if_exists)?;
+        let file = BufWriter::new(File::create(pending.tmp())?);
+        Ok(AtomicFile { file, pending })
+    }
+
+    /// Move the file into place once everything written is on disk.
+    pub(crate) fn commit(self) -> Result<PathBuf, BridgeError> {
+        self.file.into_inner().map_err(|e| e.into_error())?;
+        self.pending.commit()
+    }
+
+    /// Like [`AtomicFile::commit`], under a name only known once the file is written.
+    #[cfg(feature = "parquet_export")]
+    pub(crate) fn commit_as(mut self, path: PathBuf) -> Result<PathBuf, BridgeError> {
+        self.pending.path = path;
+        self.commit()
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Where an [`Output`] ends up.
+pub(crate) enum Sink {
+    File(AtomicFile),
+    Stdout(BufWriter<Stdout>),
+}
+
+impl Sink {
+    fn commit(self) -> Result<(), BridgeError> {
+        match self {
+            Sink::File(file) => file.commit().map(|_| ()),
+            Sink::Stdout(mut out) => Ok(out.flush()?),
+        }
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::File(file) => file.write(buf),
+            Sink::Stdout(out) => out.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::File(file) => file.flush(),
+            Sink::Stdout(out) => out.flush(),
+        }
+    }
+}
+
+/// Output of an exporter that can also write to stdout (`-`), optionally compressed.
+pub(crate) enum Output {
+    Plain(Sink),
+    Gzip(GzEncoder<Sink>),
+    Xz(XzEncoder<Sink>),
+    Zstd(zstd::Encoder<'static, Sink>),
+}
+
+impl Output {
+    /// Open `path`, compressed with `compression` at `level`, or the codec's
+    /// default level.
+    pub(crate) fn create(
+        path: &Path,
+        if_exists: IfExists,
+        compression: Compression,
+        level: Option<i32>,
+    ) -> Result<Self, BridgeError> {
+        let compression = compression.resolve(path);
+        let level = check_level(compression, level)?;
+
+        let sink = if path.as_os_str() == "-" {
+            Sink::Stdout(BufWriter::new(io::stdout()))
+        } else {
+            Sink::File(AtomicFile::create(path, if_exists)?)
+        };
+        Ok(match compression {
+            Compression::Auto | Compression::None => Output::Plain(sink),
+            Compression::Gzip => Output::Gzip(GzEncoder::new(sink, flate2::Compression::new(level.unwrap_or(6) as u32))),
+            Compression::Xz => Output::Xz(XzEncoder::new(sink, level.unwrap_or(6) as u32)),
+            Compression::Zstd => Output::Zstd(zstd::Encoder::new(sink, level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL))?),
+        })
+    }
+
+    /// Finish the compressed stream and move a file into place, or flush
+    /// stdout. Dropping an uncommitted file discards it; what went to stdout
+    /// cannot be taken back.
+    pub(crate) fn commit(self) -> Result<(), BridgeError> {
+        match self {
+            Output::Plain(sink) => sink,
+            Output::Gzip(encoder) => encoder.finish()?,
+            Output::Xz(encoder) => encoder.finish()?,
+            Output::Zstd(encoder) => encoder.finish()?,
+        }
+        .commit()
+    }
+}
+
+/// `level` if it is valid for `compression`.
+fn check_level(compression: Compression, level: Option<i32>) -> Result<Option<i32>, BridgeError> {
+    let range = match compression {
+        Compression::Gzip | Compression::Xz => 0..=9,
+        Compression::Zstd => zstd::compression_level_range(),
+        Compression::Auto | Compression::None => return Ok(None),
+    };
+    match level {
+        Some(level) if !range.contains(&level) => Err(BridgeError::Config(format!(
+            "Invalid {:?} compression level {}, expected {} to {}",
+            compression,
+            level,
+            range.start(),
+            range.end()
+        ))),
+        level => Ok(level),
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Plain(sink) => sink.write(buf),
+            Output::Gzip(encoder) => encoder.write(buf),
+            Output::Xz(encoder) => encoder.write(buf),
+            Output::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Plain(sink) => sink.flush(),
+            Output::Gzip(encoder) => encoder.flush(),
+            Output::Xz(encoder) => encoder.flush(),
+            Output::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
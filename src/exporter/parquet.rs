@@ -1,118 +1,382 @@
 #[cfg(feature = "parquet_export")]
-use std::fs::File;
+use std::path::{Path, PathBuf};
 #[cfg(feature = "parquet_export")]
-use std::path::PathBuf;
+use std::str::FromStr;
 #[cfg(feature = "parquet_export")]
 use std::sync::Arc;
 #[cfg(feature = "parquet_export")]
-use arrow::array::{Array, StringArray, BooleanArray, Float64Array};
+use arrow::array::{Array, BooleanArray, Float64Array, Int64Array, StringArray};
 #[cfg(feature = "parquet_export")]
 use arrow::record_batch::RecordBatch;
 #[cfg(feature = "parquet_export")]
 use arrow::datatypes::{Schema, Field, DataType};
 #[cfg(feature = "parquet_export")]
 use parquet::arrow::ArrowWriter;
+#[cfg(feature = "parquet_export")]
+use parquet::basic::{Compression, GzipLevel, ZstdLevel};
+#[cfg(feature = "parquet_export")]
+use parquet::file::properties::{EnabledStatistics, WriterProperties};
+#[cfg(feature = "parquet_export")]
+use parquet::format::KeyValue;
+#[cfg(feature = "parquet_export")]
+use parquet::schema::types::ColumnPath;
 use crate::error::BridgeError;
 use crate::transformer::parser::BridgeParsedAssignment;
+use crate::exporter::TableExporter;
+#[cfg(not(feature = "parquet_export"))]
 use crate::exporter::Exporter;
+#[cfg(feature = "parquet_export")]
+use crate::exporter::{AssignmentWriter, IfExists, StreamingExporter};
+#[cfg(feature = "parquet_export")]
+use crate::exporter::output::AtomicFile;
+#[cfg(feature = "parquet_export")]
+use crate::exporter::dataset::DatasetWriter;
+#[cfg(feature = "parquet_export")]
+use crate::exporter::batch::{self, BatchBuilder};
+#[cfg(feature = "parquet_export")]
+use crate::exporter::schema::{Column, ColumnSchema};
+use crate::analysis::{MetricTable, MetricValue};
 use tracing::{info, warn};
 
+/// Default number of entries per Parquet row group.
+#[cfg(feature = "parquet_export")]
+pub const DEFAULT_ROW_GROUP_SIZE: usize = 100_000;
+
+/// Parquet page compression, optionally with a codec level (`zstd:9`, `gzip:6`).
+#[cfg(feature = "parquet_export")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParquetCompression {
+    None,
+    #[default]
+    Snappy,
+    Gzip(Option<u32>),
+    Zstd(Option<i32>),
+}
+
+#[cfg(feature = "parquet_export")]
+impl ParquetCompression {
+    fn to_parquet(self) -> Result<Compression, BridgeError> {
+        let invalid = |e: parquet::errors::ParquetError| BridgeError::Config(format!("Invalid compression level: {}", e));
+        Ok(match self {
+            ParquetCompression::None => Compression::UNCOMPRESSED,
+            ParquetCompression::Snappy => Compression::SNAPPY,
+            ParquetCompression::Gzip(level) => Compression::GZIP(match level {
+                Some(level) => GzipLevel::try_new(level).map_err(invalid)?,
+                None => GzipLevel::default(),
+            }),
+            ParquetCompression::Zstd(level) => Compression::ZSTD(match level {
+                Some(level) => ZstdLevel::try_new(level).map_err(invalid)?,
+                None => ZstdLevel::default(),
+            }),
+        })
+    }
+}
+
+#[cfg(feature = "parquet_export")]
+impl FromStr for ParquetCompression {
+    type Err = BridgeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        let (codec, level) = match lower.split_once(':') {
+            Some((codec, level)) => (codec, Some(level)),
+            None => (lower.as_str(), None),
+        };
+        let level_err = || BridgeError::Config(format!("Invalid compression level in '{}'", s));
+        match (codec, level) {
+            ("none" | "uncompressed", None) => Ok(ParquetCompression::None),
+            ("snappy", None) => Ok(ParquetCompression::Snappy),
+            ("gzip", level) => Ok(ParquetCompression::Gzip(
+                level.map(|l| l.parse().map_err(|_| level_err())).transpose()?,
+            )),
+            ("zstd", level) => Ok(ParquetCompression::Zstd(
+                level.map(|l| l.parse().map_err(|_| level_err())).transpose()?,
+            )),
+            _ => Err(BridgeError::Config(format!(
+                "Unknown compression '{}', expected none, snappy, gzip[:level] or zstd[:level]",
+                s
+            ))),
+        }
+    }
+}
+
 #[cfg(feature = "parquet_export")]
 pub struct ParquetExporter {
     pub output_path: PathBuf,
+    pub schema: ColumnSchema,
+    /// Entries per row group; each group is written out as soon as it is full.
+    pub row_group_size: usize,
+    pub compression: ParquetCompression,
+    /// Write a bloom filter for `fingerprint`, so single bridges can be looked up cheaply.
+    pub bloom_filter: bool,
+    /// Treat `output_path` as the root of a Hive-partitioned dataset
+    /// (`year=YYYY/month=MM/part-*.parquet`) and only append files not yet in it.
+    pub dataset: bool,
+    /// Where the assignments came from (CollecTor URL, directory, ...), recorded in the file metadata.
+    pub source: Option<String>,
+    /// Applies to single files; a dataset is always appended to.
+    pub if_exists: IfExists,
 }
 
 #[cfg(feature = "parquet_export")]
-impl Exporter for ParquetExporter {
-    fn export(&self, data: &[BridgeParsedAssignment]) -> Result<(), BridgeError> {
-        info!("Exporting {} assignments to Parquet format...", data.len());
-        
-        if data.is_empty() {
-            warn!("⚠️ No data to export");
-            return Ok(());
+impl ParquetExporter {
+    /// Export of the default columns.
+    pub fn new(output_path: PathBuf) -> Self {
+        ParquetExporter {
+            output_path,
+            schema: ColumnSchema::default(),
+            row_group_size: DEFAULT_ROW_GROUP_SIZE,
+            compression: ParquetCompression::default(),
+            bloom_filter: true,
+            dataset: false,
+            source: None,
+            if_exists: IfExists::default(),
         }
+    }
 
-        let schema = Arc::new(Schema::new(vec![
-            Field::new("file_sha", DataType::Utf8, false),
-            Field::new("published_timestamp", DataType::Int64, false),
-            Field::new("entry_sha", DataType::Utf8, false),
-            Field::new("fingerprint", DataType::Utf8, false),
-            Field::new("distribution_method", DataType::Utf8, false),
-            Field::new("transport", DataType::Utf8, true),
-            Field::new("ip", DataType::Utf8, true),
-            Field::new("blocklist", DataType::Utf8, true),
-            Field::new("distributed", DataType::Boolean, true),
-            Field::new("state", DataType::Utf8, true),
-            Field::new("bandwidth", DataType::Utf8, true),
-            Field::new("ratio", DataType::Float64, true),
-        ]));
-
-        let mut file_shas = Vec::new();
-        let mut timestamps = Vec::new();
-        let mut entry_shas = Vec::new();
-        let mut fingerprints = Vec::new();
-        let mut methods = Vec::new();
-        let mut transports = Vec::new();
-        let mut ips = Vec::new();
-        let mut blocklists = Vec::new();
-        let mut distributed = Vec::new();
-        let mut states = Vec::new();
-        let mut bandwidths = Vec::new();
-        let mut ratios = Vec::new();
-
-        let total_entries = data.iter().map(|a| a.lines.len()).sum::<usize>();
-        info!(" Processing {} total entries...", total_entries);
-
-        for assignment in data {
-            for line in &assignment.lines {
-                file_shas.push(assignment.file_sha.as_str());
-                timestamps.push(Some(assignment.published));
-                entry_shas.push(line.sha.as_str());
-                fingerprints.push(line.fingerprint.as_str());
-                methods.push(line.distribution_method.as_str());
-                transports.push(line.transport.as_deref());
-                ips.push(line.ip.as_deref());
-                blocklists.push(line.blocklist.as_deref());
-                distributed.push(line.distributed);
-                states.push(line.state.as_deref());
-                bandwidths.push(line.bandwidth.as_deref());
-                ratios.push(line.ratio.map(|r| r as f64));
+    /// Writer properties for entry exports: dictionary encoding only for
+    /// low-cardinality columns, page statistics and the fingerprint bloom filter.
+    fn properties(&self) -> Result<WriterProperties, BridgeError> {
+        let mut builder = WriterProperties::builder()
+            .set_compression(self.compression.to_parquet()?)
+            .set_max_row_group_size(self.row_group_size.max(1))
+            .set_statistics_enabled(EnabledStatistics::Page)
+            .set_dictionary_enabled(false)
+            .set_created_by(format!("bridge-parser version {}", env!("CARGO_PKG_VERSION")))
+            .set_key_value_metadata(Some(
+                batch::metadata(self.source.as_deref()).into_iter().map(|(k, v)| KeyValue::new(k, v)).collect(),
+            ));
+        for column in &self.schema.columns {
+            if column.low_cardinality() {
+                builder = builder.set_column_dictionary_enabled(ColumnPath::from(column.name()), true);
             }
         }
+        if self.bloom_filter && self.schema.columns.contains(&Column::Fingerprint) {
+            let fingerprint = ColumnPath::from(Column::Fingerprint.name());
+            builder = builder
+                .set_column_bloom_filter_enabled(fingerprint.clone(), true)
+                .set_column_bloom_filter_ndv(fingerprint, self.row_group_size.max(1) as u64);
+        }
+        Ok(builder.build())
+    }
 
-        let arrays: Vec<Arc<dyn Array>> = vec![
-            Arc::new(StringArray::from(file_shas)),
-            Arc::new(arrow::array::Int64Array::from(timestamps)),
-            Arc::new(StringArray::from(entry_shas)),
-            Arc::new(StringArray::from(fingerprints)),
-            Arc::new(StringArray::from(methods)),
-            Arc::new(StringArray::from(transports)),
-            Arc::new(StringArray::from(ips)),
-            Arc::new(StringArray::from(blocklists)),
-            Arc::new(BooleanArray::from(distributed)),
-            Arc::new(StringArray::from(states)),
-            Arc::new(StringArray::from(bandwidths)),
-            Arc::new(Float64Array::from(ratios)),
-        ];
+    /// Open `output_path` for writing assignments one file at a time.
+    pub fn writer(&self) -> Result<ParquetStreamWriter, BridgeError> {
+        let batch = BatchBuilder::new(&self.schema, batch::metadata(self.source.as_deref()), true);
 
         info!(" Creating Parquet file: {}", self.output_path.display());
+        let file = AtomicFile::create(&self.output_path, self.if_exists)?;
+        let writer = ArrowWriter::try_new(file, batch.schema(), Some(self.properties()?))
+            .map_err(|e| BridgeError::Export(format!("Failed to create writer: {}", e)))?;
+
+        Ok(ParquetStreamWriter {
+            writer,
+            batch,
+            row_group_size: self.row_group_size.max(1),
+            rows: 0,
+        })
+    }
+}
+
+/// Incremental Parquet writer from [`ParquetExporter::writer`]. Entries are
+/// buffered column by column and written out one row group at a time, so
+/// memory stays bounded by the row group size.
+#[cfg(feature = "parquet_export")]
+pub struct ParquetStreamWriter {
+    writer: ArrowWriter<AtomicFile>,
+    batch: BatchBuilder,
+    row_group_size: usize,
+    rows: usize,
+}
+
+#[cfg(feature = "parquet_export")]
+impl ParquetStreamWriter {
+    /// Append the entries of one file, writing every row group that fills up.
+    pub fn write(&mut self, file: &BridgeParsedAssignment) -> Result<(), BridgeError> {
+        for line in &file.lines {
+            self.batch.append(file, line);
+            if self.batch.len() == self.row_group_size {
+                self.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), BridgeError> {
+        let batch = match self.batch.finish()? {
+            Some(batch) => batch,
+            None => return Ok(()),
+        };
+        self.writer.write(&batch)
+            .map_err(|e| BridgeError::Export(format!("Failed to write batch: {}", e)))?;
+        self.writer.flush()
+            .map_err(|e| BridgeError::Export(format!("Failed to write row group: {}", e)))?;
+        self.rows += batch.num_rows();
+        Ok(())
+    }
 
+    /// Write the last, partial row group and the footer, then move the file
+    /// into place; returns the number of entries. A writer dropped before
+    /// this leaves nothing behind.
+    pub fn finish(self) -> Result<usize, BridgeError> {
+        let (file, rows) = self.close()?;
+        file.commit()?;
+        Ok(rows)
+    }
+
+    /// Like [`ParquetStreamWriter::finish`], but move the file to `path`.
+    pub(crate) fn finish_as(self, path: &Path) -> Result<usize, BridgeError> {
+        let (file, rows) = self.close()?;
+        file.commit_as(path.to_path_buf())?;
+        Ok(rows)
+    }
+
+    fn close(mut self) -> Result<(AtomicFile, usize), BridgeError> {
+        self.flush()?;
+        let file = self.writer.into_inner()
+            .map_err(|e| BridgeError::Export(format!("Failed to close writer: {}", e)))?;
+        Ok((file, self.rows))
+    }
+}
+
+/// Open output of a [`ParquetExporter`]. A single file is only created once
+/// the first entry arrives, so an export without entries writes nothing.
+#[cfg(feature = "parquet_export")]
+pub struct ParquetWriter<'a> {
+    exporter: &'a ParquetExporter,
+    output: ParquetOutput<'a>,
+}
+
+#[cfg(feature = "parquet_export")]
+enum ParquetOutput<'a> {
+    File(Option<Box<ParquetStreamWriter>>),
+    Dataset(DatasetWriter<'a>),
+}
+
+#[cfg(feature = "parquet_export")]
+impl StreamingExporter for ParquetExporter {
+    type Writer<'a> = ParquetWriter<'a>;
+
+    async fn begin(&self) -> Result<ParquetWriter<'_>, BridgeError> {
+        let output = if self.dataset {
+            ParquetOutput::Dataset(DatasetWriter::open(self)?)
+        } else {
+            ParquetOutput::File(None)
+        };
+        Ok(ParquetWriter { exporter: self, output })
+    }
+}
+
+#[cfg(feature = "parquet_export")]
+impl AssignmentWriter for ParquetWriter<'_> {
+    async fn write_assignment(&mut self, assignment: &BridgeParsedAssignment) -> Result<(), BridgeError> {
+        match self.output {
+            ParquetOutput::Dataset(ref mut dataset) => dataset.write(assignment),
+            ParquetOutput::File(ref mut writer) => {
+                if assignment.lines.is_empty() {
+                    return Ok(());
+                }
+                let writer = match writer {
+                    Some(writer) => writer,
+                    empty => empty.insert(Box::new(self.exporter.writer()?)),
+                };
+                writer.write(assignment)
+            }
+        }
+    }
+
+    async fn finish(self) -> Result<(), BridgeError> {
+        match self.output {
+            ParquetOutput::Dataset(dataset) => dataset.finish().map(|_| ()),
+            ParquetOutput::File(None) => {
+                warn!("⚠️ No entries to export");
+                Ok(())
+            }
+            ParquetOutput::File(Some(writer)) => {
+                let rows = writer.finish()?;
+                info!("✅ Successfully exported {} entries to Parquet format", rows);
+                Ok(())
+            }
+        }
+    }
+
+    async fn abort(self) -> Result<(), BridgeError> {
+        match self.output {
+            ParquetOutput::Dataset(dataset) => dataset.abort(),
+            ParquetOutput::File(writer) => {
+                drop(writer);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "parquet_export")]
+impl TableExporter for ParquetExporter {
+    fn export_table(&self, table: &MetricTable) -> Result<(), BridgeError> {
+        info!("Exporting {} rows of '{}' to Parquet format...", table.rows.len(), table.name);
+
+        let mut fields = Vec::with_capacity(table.columns.len());
+        let mut arrays: Vec<Arc<dyn Array>> = Vec::with_capacity(table.columns.len());
+
+        for (idx, name) in table.columns.iter().enumerate() {
+            let column = table.rows.iter().map(|row| &row[idx]);
+            // The first non-null cell decides the column type; text is the fallback.
+            let kind = column.clone().find(|v| **v != MetricValue::Null);
+            let (data_type, array): (DataType, Arc<dyn Array>) = match kind {
+                Some(MetricValue::Int(_)) => (
+                    DataType::Int64,
+                    Arc::new(Int64Array::from(column.map(|v| match v {
+                        MetricValue::Int(i) => Some(*i),
+                        _ => None,
+                    }).collect::<Vec<_>>())),
+                ),
+                Some(MetricValue::Float(_)) => (
+                    DataType::Float64,
+                    Arc::new(Float64Array::from(column.map(|v| match v {
+                        MetricValue::Float(f) => Some(*f),
+                        MetricValue::Int(i) => Some(*i as f64),
+                        _ => None,
+                    }).collect::<Vec<_>>())),
+                ),
+                Some(MetricValue::Bool(_)) => (
+                    DataType::Boolean,
+                    Arc::new(BooleanArray::from(column.map(|v| match v {
+                        MetricValue::Bool(b) => Some(*b),
+                        _ => None,
+                    }).collect::<Vec<_>>())),
+                ),
+                _ => (
+                    DataType::Utf8,
+                    Arc::new(StringArray::from(column.map(|v| match v {
+                        MetricValue::Null => None,
+                        other => Some(other.to_string()),
+                    }).collect::<Vec<_>>())),
+                ),
+            };
+            fields.push(Field::new(name, data_type, true));
+            arrays.push(array);
+        }
+
+        let schema = Arc::new(Schema::new(fields));
         let batch = RecordBatch::try_new(schema.clone(), arrays)
             .map_err(|e| BridgeError::Export(format!("Failed to create record batch: {}", e)))?;
 
-        let file = File::create(&self.output_path)
-            .map_err(|e| BridgeError::Export(format!("Failed to create file: {}", e)))?;
+        let file = AtomicFile::create(&self.output_path, self.if_exists)?;
 
-        let mut writer = ArrowWriter::try_new(file, schema, None)
+        let properties = WriterProperties::builder()
+            .set_compression(self.compression.to_parquet()?)
+            .build();
+        let mut writer = ArrowWriter::try_new(file, schema, Some(properties))
             .map_err(|e| BridgeError::Export(format!("Failed to create writer: {}", e)))?;
 
         writer.write(&batch)
             .map_err(|e| BridgeError::Export(format!("Failed to write batch: {}", e)))?;
 
-        writer.close()
-            .map_err(|e| BridgeError::Export(format!("Failed to close writer: {}", e)))?;
+        writer.into_inner()
+            .map_err(|e| BridgeError::Export(format!("Failed to close writer: {}", e)))?
+            .commit()?;
 
-        info!("✅ Successfully exported to Parquet format");
         Ok(())
     }
 }
@@ -126,3 +390,10 @@ impl Exporter for ParquetExporter {
         Err(BridgeError::Export("Parquet export support not enabled. Compile with --features parquet_export".into()))
     }
 }
+
+#[cfg(not(feature = "parquet_export"))]
+impl TableExporter for ParquetExporter {
+    fn export_table(&self, _table: &MetricTable) -> Result<(), BridgeError> {
+        Err(BridgeError::Export("Parquet export support not enabled. Compile with --features parquet_export".into()))
+    }
+}
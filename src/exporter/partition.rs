@@ -0,0 +1,368 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use chrono::{Datelike, Months, NaiveDate, NaiveDateTime};
+use tokio_postgres::Client;
+use tracing::{info, warn};
+use crate::error::BridgeError;
+use crate::exporter::pg_views;
+#[cfg(feature = "parquet_export")]
+use crate::exporter::AssignmentWriter;
+#[cfg(feature = "parquet_export")]
+use crate::transformer::{BridgeLineEntry, BridgeParsedAssignment};
+
+/// Prefix of the monthly partitions of `bridge_entry`, followed by `yYYYYmMM`.
+const PARTITION_PREFIX: &str = "bridge_entry_y";
+
+/// Cutoff for `prune`: an absolute date or an age relative to today.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OlderThan {
+    Date(NaiveDate),
+    Days(u32),
+    Months(u32),
+}
+
+impl OlderThan {
+    /// The first day that is kept, counted back from `today` for ages.
+    pub fn cutoff(&self, today: NaiveDate) -> NaiveDate {
+        match *self {
+            OlderThan::Date(date) => date,
+            OlderThan::Days(days) => today - chrono::Duration::days(days as i64),
+            OlderThan::Months(months) => today - Months::new(months),
+        }
+    }
+}
+
+impl FromStr for OlderThan {
+    type Err = BridgeError;
+
+    /// `YYYY-MM-DD`, or a number followed by `d` (days), `w` (weeks), `m` (months) or `y` (years).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return Ok(OlderThan::Date(date));
+        }
+
+        let invalid = || {
+            BridgeError::Config(format!(
+                "Invalid age '{}', expected YYYY-MM-DD or a number followed by d, w, m or y",
+                s
+            ))
+        };
+        let split = s.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+        let count: u32 = s[..split].parse().map_err(|_| invalid())?;
+        match &s[split..] {
+            "d" => Ok(OlderThan::Days(count)),
+            "w" => Ok(OlderThan::Days(count * 7)),
+            "m" => Ok(OlderThan::Months(count)),
+            "y" => Ok(OlderThan::Months(count * 12)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// What `prune` does with partitions older than the cutoff.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum PruneAction {
+    /// Detach them into standalone tables, keeping the rows.
+    #[default]
+    Detach,
+    /// Drop them with their rows.
+    Drop,
+    /// Write each one to `<dir>/<partition>.parquet`, then drop it.
+    Archive(PathBuf),
+}
+
+/// One monthly partition of `bridge_entry`, covering `[from, to)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Partition {
+    pub name: String,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// Rows in the partition; only counted for partitions being pruned.
+    pub rows: i64,
+}
+
+/// First day of the month containing `date`.
+fn month_start(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).expect("every month has a first day")
+}
+
+fn partition_name(month: NaiveDate) -> String {
+    format!("{}{:04}m{:02}", PARTITION_PREFIX, month.year(), month.month())
+}
+
+/// Month covered by a partition, if `name` follows the `bridge_entry_yYYYYmMM` scheme.
+fn partition_month(name: &str) -> Option<NaiveDate> {
+    let (year, month) = name.strip_prefix(PARTITION_PREFIX)?.split_once('m')?;
+    NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, 1)
+}
+
+/// Whether `bridge_entry` is a partitioned table.
+pub(crate) async fn is_partitioned(tx: &Client) -> Result<bool, BridgeError> {
+    let row = tx
+        .query_one("SELECT relkind = 'p' FROM pg_class WHERE oid = 'bridge_entry'::regclass", &[])
+        .await
+        .map_err(|e| BridgeError::Database(format!("Inspecting bridge_entry failed: {}", e)))?;
+    Ok(row.get(0))
+}
+
+/// Turn a plain `bridge_entry` into one partitioned by month on `published`,
+/// moving its rows. Does nothing if it is partitioned already.
+///
+/// The primary key becomes `(sha, published)`, as PostgreSQL requires the
+/// partition key in every unique constraint. Summary views depend on the old
+/// table and are dropped; the export recreates the selected ones.
+pub(crate) async fn partition_entries(tx: &Client) -> Result<(), BridgeError> {
+    if is_partitioned(tx).await? {
+        return Ok(());
+    }
+
+    let convert = |e: tokio_postgres::Error| {
+        BridgeError::Database(format!("Partitioning bridge_entry failed: {}", e))
+    };
+    pg_views::drop_flat(tx).await?;
+    tx.batch_execute(
+        "ALTER TABLE bridge_entry RENAME TO bridge_entry_unpartitioned;
+         ALTER INDEX bridge_entry_pkey RENAME TO bridge_entry_unpartitioned_pkey;
+         DROP INDEX IF EXISTS bridge_entry_fingerprint_idx, bridge_entry_published_idx;
+
+         CREATE TABLE bridge_entry (
+             sha TEXT NOT NULL,
+             fingerprint TEXT NOT NULL,
+             method TEXT NOT NULL,
+             file_sha TEXT REFERENCES bridge_file(sha),
+             transport TEXT,
+             ip TEXT,
+             block TEXT,
+             distributed BOOLEAN,
+             state TEXT,
+             bandwidth TEXT,
+             ratio REAL,
+             published TIMESTAMP NOT NULL,
+             PRIMARY KEY (sha, published)
+         ) PARTITION BY RANGE (published);
+         CREATE INDEX bridge_entry_fingerprint_idx ON bridge_entry (fingerprint);
+         CREATE INDEX bridge_entry_published_idx ON bridge_entry (published);",
+    )
+    .await
+    .map_err(convert)?;
+
+    let rows = tx
+        .query("SELECT DISTINCT date_trunc('month', published) FROM bridge_entry_unpartitioned", &[])
+        .await
+        .map_err(convert)?;
+    let months: Vec<NaiveDate> = rows.iter().map(|row| row.get::<_, NaiveDateTime>(0).date()).collect();
+    ensure_partitions(tx, months).await?;
+
+    let moved = tx
+        .execute("INSERT INTO bridge_entry SELECT * FROM bridge_entry_unpartitioned", &[])
+        .await
+        .map_err(convert)?;
+    tx.batch_execute("DROP TABLE bridge_entry_unpartitioned")
+        .await
+        .map_err(convert)?;
+
+    info!(rows = moved, "Partitioned bridge_entry by month");
+    Ok(())
+}
+
+/// Create the monthly partitions covering `dates` that do not exist yet.
+pub(crate) async fn ensure_partitions(
+    tx: &Client,
+    dates: impl IntoIterator<Item = NaiveDate>,
+) -> Result<(), BridgeError> {
+    let existing = partitions(tx).await?;
+    let mut months: Vec<NaiveDate> = dates.into_iter().map(month_start).collect();
+    months.sort();
+    months.dedup();
+
+    for month in months.into_iter().filter(|m| !existing.iter().any(|p| p.from == *m)) {
+        let name = partition_name(month);
+        let next = month + Months::new(1);
+        tx.batch_execute(&format!(
+            "CREATE TABLE {} PARTITION OF bridge_entry FOR VALUES FROM ('{}') TO ('{}')",
+            name, month, next
+        ))
+        .await
+        .map_err(|e| {
+            BridgeError::Database(format!(
+                "Creating partition {} failed (a detached partition must be reattached or dropped first): {}",
+                name, e
+            ))
+        })?;
+        info!("Created partition {}", name);
+    }
+
+    Ok(())
+}
+
+/// Attached monthly partitions of `bridge_entry`, oldest first.
+pub(crate) async fn partitions(tx: &Client) -> Result<Vec<Partition>, BridgeError> {
+    let rows = tx
+        .query(
+            "SELECT c.relname::text FROM pg_inherits i
+             JOIN pg_class c ON c.oid = i.inhrelid
+             WHERE i.inhparent = 'bridge_entry'::regclass",
+            &[],
+        )
+        .await
+        .map_err(|e| BridgeError::Database(format!("Listing partitions failed: {}", e)))?;
+
+    let mut partitions: Vec<Partition> = rows
+        .iter()
+        .filter_map(|row| {
+            let name: String = row.get(0);
+            match partition_month(&name) {
+                Some(from) => Some(Partition {
+                    name,
+                    from,
+                    to: from + Months::new(1),
+                    rows: 0,
+                }),
+                None => {
+                    warn!("Ignoring partition {} that is not named by month", name);
+                    None
+                }
+            }
+        })
+        .collect();
+    partitions.sort_by_key(|p| p.from);
+    Ok(partitions)
+}
+
+/// Detach, drop or archive every partition that ends on or before `cutoff`,
+/// returning the partitions affected. With `dry_run` they are only listed.
+pub(crate) async fn prune(
+    tx: &Client,
+    cutoff: NaiveDate,
+    action: &PruneAction,
+    dry_run: bool,
+) -> Result<Vec<Partition>, BridgeError> {
+    if !is_partitioned(tx).await? {
+        return Err(BridgeError::Config(
+            "bridge_entry is not partitioned; export once with --pg-partition first".into(),
+        ));
+    }
+
+    let mut old: Vec<Partition> = partitions(tx).await?.into_iter().filter(|p| p.to <= cutoff).collect();
+    for partition in &mut old {
+        let row = tx
+            .query_one(&format!("SELECT COUNT(*) FROM {}", partition.name), &[])
+            .await
+            .map_err(|e| BridgeError::Database(format!("Counting rows of {} failed: {}", partition.name, e)))?;
+        partition.rows = row.get(0);
+    }
+    if dry_run {
+        return Ok(old);
+    }
+
+    for partition in &old {
+        let statement = match action {
+            PruneAction::Detach => format!("ALTER TABLE bridge_entry DETACH PARTITION {}", partition.name),
+            PruneAction::Drop => format!("DROP TABLE {}", partition.name),
+            PruneAction::Archive(dir) => {
+                archive(tx, partition, dir).await?;
+                format!("DROP TABLE {}", partition.name)
+            }
+        };
+        tx.batch_execute(&statement)
+            .await
+            .map_err(|e| BridgeError::Database(format!("Pruning {} failed: {}", partition.name, e)))?;
+        info!(rows = partition.rows, "Pruned partition {} ({:?})", partition.name, action);
+    }
+
+    Ok(old)
+}
+
+/// Read a partition back file by file, in publication order, and write each
+/// file to `writer` before reading the next, so only one file is held in memory.
+#[cfg(feature = "parquet_export")]
+async fn write_partition<W: AssignmentWriter>(
+    tx: &Client,
+    partition: &Partition,
+    writer: &mut W,
+) -> Result<(), BridgeError> {
+    use futures_util::TryStreamExt;
+
+    let read_error = |e: tokio_postgres::Error| BridgeError::Database(format!("Reading {} failed: {}", partition.name, e));
+    let rows = tx
+        .query_raw(
+            &format!(
+                "SELECT e.file_sha, f.header, e.published, e.sha, e.fingerprint, e.method,
+                        e.transport, e.ip, e.block, e.distributed, e.state, e.bandwidth, e.ratio
+                 FROM {} e JOIN bridge_file f ON f.sha = e.file_sha
+                 ORDER BY e.published, e.file_sha, e.sha",
+                partition.name
+            ),
+            std::iter::empty::<i32>(),
+        )
+        .await
+        .map_err(read_error)?;
+    let mut rows = std::pin::pin!(rows);
+
+    let mut file: Option<BridgeParsedAssignment> = None;
+    while let Some(row) = rows.try_next().await.map_err(read_error)? {
+        let file_sha: String = row.get(0);
+        if file.as_ref().is_none_or(|f| f.file_sha != file_sha) {
+            if let Some(done) = file.take() {
+                writer.write_assignment(&done).await?;
+            }
+            let published: NaiveDateTime = row.get(2);
+            file = Some(BridgeParsedAssignment {
+                file_sha,
+                published: published.and_utc().timestamp_millis(),
+                header: row.get(1),
+                lines: Vec::new(),
+            });
+        }
+        if let Some(ref mut file) = file {
+            file.lines.push(BridgeLineEntry {
+                sha: row.get(3),
+                fingerprint: row.get(4),
+                distribution_method: row.get(5),
+                transport: row.get(6),
+                ip: row.get(7),
+                blocklist: row.get(8),
+                distributed: row.get(9),
+                state: row.get(10),
+                bandwidth: row.get(11),
+                ratio: row.get(12),
+            });
+        }
+    }
+    if let Some(done) = file {
+        writer.write_assignment(&done).await?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "parquet_export")]
+async fn archive(tx: &Client, partition: &Partition, dir: &std::path::Path) -> Result<(), BridgeError> {
+    use crate::exporter::schema::{Column, ColumnSchema};
+    use crate::exporter::{abort_after, ParquetCompression, ParquetExporter, StreamingExporter};
+
+    std::fs::create_dir_all(dir)?;
+    let exporter = ParquetExporter {
+        schema: ColumnSchema {
+            columns: Column::ALL.to_vec(),
+            ..ColumnSchema::default()
+        },
+        compression: ParquetCompression::Zstd(None),
+        source: Some(format!("PostgreSQL partition {}", partition.name)),
+        ..ParquetExporter::new(dir.join(format!("{}.parquet", partition.name)))
+    };
+    let mut writer = exporter.begin().await?;
+    match write_partition(tx, partition, &mut writer).await {
+        Ok(()) => writer.finish().await,
+        Err(e) => {
+            abort_after(writer, &e).await;
+            Err(e)
+        }
+    }
+}
+
+#[cfg(not(feature = "parquet_export"))]
+async fn archive(_tx: &Client, _partition: &Partition, _dir: &std::path::Path) -> Result<(), BridgeError> {
+    Err(BridgeError::Config(
+        "Archiving partitions needs Parquet support; compile with --features parquet_export".into(),
+    ))
+}
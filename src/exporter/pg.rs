@@ -1,92 +1,646 @@
 use crate::transformer::{BridgeParsedAssignment, BridgeLineEntry};
 use crate::error::BridgeError;
-use chrono::{DateTime, NaiveDateTime, Utc};
-use tokio_postgres::{NoTls, Transaction};
-use crate::exporter::Exporter;
-
-/// Write parsed bridge assignments into PostgreSQL.
-pub async fn write_to_postgres(
-    items: Vec<BridgeParsedAssignment>,
-    conn_str: &str,
-    truncate: bool,
-) -> Result<(), BridgeError> {
-    let (mut client, connection) = tokio_postgres::connect(conn_str, NoTls)
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, Utc};
+use std::collections::HashSet;
+use std::future::Future;
+use std::pin::pin;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio_postgres::binary_copy::BinaryCopyInWriter;
+use tokio_postgres::types::{ToSql, Type};
+use tokio_postgres::{Client, NoTls};
+use tracing::{debug, info};
+use crate::exporter::{abort_after, block_on, AssignmentWriter, StreamingExporter};
+use crate::exporter::pg_norm;
+use crate::exporter::pg_views::{self, SummaryView};
+use crate::exporter::partition::{self, Partition, PruneAction};
+use crate::exporter::tls::{take_verify_mode, SslMode, TlsConfig};
+use crate::exporter::migrate::{migrate_up, migration_status, Migration, MigrationStatus};
+
+/// How bridge_entry rows are loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoadMode {
+    /// `COPY ... FROM STDIN (FORMAT binary)` into a staging table, merged in batches.
+    #[default]
+    Copy,
+    /// One `INSERT` round trip per entry.
+    Insert,
+}
+
+impl FromStr for LoadMode {
+    type Err = BridgeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "copy" => Ok(LoadMode::Copy),
+            "insert" => Ok(LoadMode::Insert),
+            other => Err(BridgeError::Config(format!("Unknown load mode '{}', expected copy or insert", other))),
+        }
+    }
+}
+
+/// Table layout written by the PostgreSQL exporter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    /// `bridge_file` and `bridge_entry` with one text column per field.
+    #[default]
+    Flat,
+    /// The `bridge_norm` schema: a `bridge` dimension, lookup tables, arrays and
+    /// `timestamptz`, plus views in the flat shape.
+    Normalized,
+}
+
+impl FromStr for Layout {
+    type Err = BridgeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "flat" => Ok(Layout::Flat),
+            "normalized" | "normalised" => Ok(Layout::Normalized),
+            other => Err(BridgeError::Config(format!("Unknown layout '{}', expected flat or normalized", other))),
+        }
+    }
+}
+
+impl Layout {
+    pub(crate) fn file_table(&self) -> &'static str {
+        match self {
+            Layout::Flat => "bridge_file",
+            Layout::Normalized => "bridge_norm.file",
+        }
+    }
+
+    pub(crate) fn entry_table(&self) -> &'static str {
+        match self {
+            Layout::Flat => "bridge_entry",
+            Layout::Normalized => "bridge_norm.assignment",
+        }
+    }
+}
+
+/// What happens to files and entries that are already in the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportMode {
+    /// Insert new rows and leave existing ones untouched.
+    #[default]
+    Append,
+    /// Leave out every file whose `sha` is already loaded.
+    SkipExisting,
+    /// Insert new rows and update existing ones whose columns changed.
+    Upsert,
+    /// Delete the entries of already loaded files and insert them again.
+    ReplaceFile,
+}
+
+impl FromStr for ExportMode {
+    type Err = BridgeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "append" => Ok(ExportMode::Append),
+            "skip-existing" | "skip_existing" => Ok(ExportMode::SkipExisting),
+            "upsert" => Ok(ExportMode::Upsert),
+            "replace-file" | "replace_file" => Ok(ExportMode::ReplaceFile),
+            other => Err(BridgeError::Config(format!(
+                "Unknown export mode '{}', expected append, skip-existing, upsert or replace-file",
+                other
+            ))),
+        }
+    }
+}
+
+/// Counters and timings of one load into bridge_entry.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadStats {
+    pub batches: usize,
+    pub rows_sent: u64,
+    pub rows_inserted: u64,
+    pub rows_updated: u64,
+    /// Entries left as they were: unchanged duplicates and entries of skipped files.
+    pub rows_skipped: u64,
+    /// Entries removed by `ExportMode::ReplaceFile` before reinserting.
+    pub rows_deleted: u64,
+    pub files_skipped: usize,
+    pub copy_time: Duration,
+    pub merge_time: Duration,
+    /// Time spent refreshing materialised views after the commit.
+    pub refresh_time: Duration,
+}
+
+/// Build an `INSERT INTO table (columns) <source>` that resolves conflicts on
+/// the `key` columns according to `mode` and selects the `(inserted, updated)` row counts.
+pub(crate) fn merge_statement(table: &str, columns: &[&str], key: &[&str], source: &str, mode: ExportMode) -> String {
+    let conflict = match mode {
+        ExportMode::Upsert => {
+            let updatable: Vec<&str> = columns.iter().copied().filter(|c| !key.contains(c)).collect();
+            let target: Vec<String> = updatable.iter().map(|c| format!("target.{}", c)).collect();
+            let excluded: Vec<String> = updatable.iter().map(|c| format!("EXCLUDED.{}", c)).collect();
+            format!(
+                "ON CONFLICT ({}) DO UPDATE SET ({}) = ROW({}) WHERE ({}) IS DISTINCT FROM ({})",
+                key.join(", "),
+                updatable.join(", "),
+                excluded.join(", "),
+                target.join(", "),
+                excluded.join(", ")
+            )
+        }
+        _ => "ON CONFLICT DO NOTHING".to_string(),
+    };
+
+    // The outer query runs on the snapshot taken before the insert, so a
+    // returned key that already exists there was updated, not inserted
+    let returned: Vec<String> = key.iter().map(|c| format!("merged.{}", c)).collect();
+    let existing: Vec<String> = key.iter().map(|c| format!("existing.{}", c)).collect();
+    format!(
+        "WITH merged AS (
+            INSERT INTO {table} AS target ({}) {} {} RETURNING {}
+        )
+        SELECT count(*) FILTER (WHERE NOT updated), count(*) FILTER (WHERE updated) FROM (
+            SELECT EXISTS (SELECT 1 FROM {table} existing WHERE ({}) = ({})) AS updated FROM merged
+        ) counted",
+        columns.join(", "),
+        source,
+        conflict,
+        key.join(", "),
+        existing.join(", "),
+        returned.join(", "),
+    )
+}
+
+/// Run a statement from [`merge_statement`] and return `(inserted, updated)`.
+pub(crate) async fn run_merge(
+    tx: &Client,
+    statement: &str,
+    params: &[&(dyn ToSql + Sync)],
+) -> Result<(u64, u64), BridgeError> {
+    let row = tx
+        .query_one(statement, params)
         .await
-        .map_err(|e| BridgeError::Database(format!("PostgreSQL connection failed: {}", e)))?;
+        .map_err(|e| BridgeError::Database(format!("Merging entries failed: {}", e)))?;
+    let (inserted, updated): (i64, i64) = (row.get(0), row.get(1));
+    Ok((inserted as u64, updated as u64))
+}
+
+/// Columns of bridge_entry, in COPY and insert order.
+const ENTRY_COLUMNS: [&str; 12] = [
+    "sha", "fingerprint", "method", "file_sha",
+    "transport", "ip", "block", "distributed",
+    "state", "bandwidth", "ratio", "published",
+];
+
+/// Primary key of bridge_entry; partitioned tables must include the partition key.
+fn entry_key(partitioned: bool) -> &'static [&'static str] {
+    if partitioned {
+        &["sha", "published"]
+    } else {
+        &["sha"]
+    }
+}
+
+/// Open a connection, with TLS according to `tls`, and drive it on a background task.
+async fn connect(conn_str: &str, tls: &TlsConfig) -> Result<Client, BridgeError> {
+    let (conn_str, verify) = take_verify_mode(conn_str);
+    let mut config: tokio_postgres::Config = conn_str
+        .parse()
+        .map_err(|e| BridgeError::Config(format!("Invalid PostgreSQL connection string: {}", e)))?;
+    let mut mode = tls
+        .mode
+        .or(verify)
+        .unwrap_or_else(|| SslMode::from_postgres(config.get_ssl_mode()));
+    // Like libpq, never negotiate TLS over Unix-domain sockets
+    if is_unix_socket_only(&config) {
+        mode = SslMode::Disable;
+    }
+    config.ssl_mode(mode.to_postgres());
 
-    // Spawn a background task to monitor connection errors.
+    let client = match tls.connector(mode)? {
+        Some(connector) => {
+            let (client, connection) = config
+                .connect(connector)
+                .await
+                .map_err(|e| BridgeError::Database(format!("PostgreSQL connection failed: {}", e)))?;
+            spawn_connection(connection);
+            client
+        }
+        None => {
+            let (client, connection) = config
+                .connect(NoTls)
+                .await
+                .map_err(|e| BridgeError::Database(format!("PostgreSQL connection failed: {}", e)))?;
+            spawn_connection(connection);
+            client
+        }
+    };
+
+    Ok(client)
+}
+
+#[cfg(unix)]
+fn is_unix_socket_only(config: &tokio_postgres::Config) -> bool {
+    let hosts = config.get_hosts();
+    !hosts.is_empty() && hosts.iter().all(|h| matches!(h, tokio_postgres::config::Host::Unix(_)))
+}
+
+#[cfg(not(unix))]
+fn is_unix_socket_only(_config: &tokio_postgres::Config) -> bool {
+    false
+}
+
+/// Spawn a background task that drives the connection and reports its errors.
+fn spawn_connection<C>(connection: C)
+where
+    C: Future<Output = Result<(), tokio_postgres::Error>> + Send + 'static,
+{
     tokio::spawn(async move {
         if let Err(e) = connection.await {
             tracing::error!("PostgreSQL async error: {e}");
         }
     });
+}
 
-    let tx = client.transaction()
+/// Start a transaction on `client`. It lasts until [`commit`] or
+/// [`rollback`]; closing the connection rolls it back.
+pub(crate) async fn begin(client: &Client) -> Result<(), BridgeError> {
+    client
+        .batch_execute("BEGIN")
         .await
-        .map_err(|e| BridgeError::Database(format!("Begin transaction failed: {}", e)))?;
+        .map_err(|e| BridgeError::Database(format!("Begin transaction failed: {}", e)))
+}
 
-    prepare_schema(&tx).await?;
+pub(crate) async fn commit(client: &Client) -> Result<(), BridgeError> {
+    client
+        .batch_execute("COMMIT")
+        .await
+        .map_err(|e| BridgeError::Database(format!("Commit failed: {}", e)))
+}
 
-    if truncate {
-        tx.execute("TRUNCATE TABLE bridge_entry, bridge_file", &[])
-            .await
-            .map_err(|e| BridgeError::Database(format!("Failed to truncate tables: {}", e)))?;
+pub(crate) async fn rollback(client: &Client) -> Result<(), BridgeError> {
+    client
+        .batch_execute("ROLLBACK")
+        .await
+        .map_err(|e| BridgeError::Database(format!("Rollback failed: {}", e)))
+}
+
+/// Open load of a [`PostgresExporter`]: one transaction from `begin` to
+/// `finish`, with written files merged whenever `batch_size` entries are pending.
+pub struct PostgresWriter<'a> {
+    config: &'a PostgresExporter,
+    client: Client,
+    /// Whether bridge_entry is partitioned by month (flat layout).
+    partitioned: bool,
+    /// `(year, month)`s whose partitions are known to exist.
+    months: HashSet<(i32, u32)>,
+    /// Files written so far; the same file twice would make one upsert touch a row twice.
+    seen: HashSet<String>,
+    /// Files written but not merged yet, with their number of entries.
+    pending: Vec<BridgeParsedAssignment>,
+    pending_rows: usize,
+    stats: LoadStats,
+    started: Instant,
+}
+
+impl StreamingExporter for PostgresExporter {
+    type Writer<'a> = PostgresWriter<'a>;
+
+    async fn begin(&self) -> Result<PostgresWriter<'_>, BridgeError> {
+        let started = Instant::now();
+        let client = connect(&self.conn_str, &self.tls).await?;
+        begin(&client).await?;
+
+        // Bring the schema up to date; refuses databases migrated by a newer binary
+        migrate_up(&client, false).await?;
+
+        if self.partition {
+            if self.layout != Layout::Flat {
+                return Err(BridgeError::Config("Partitioning is only supported for the flat layout".into()));
+            }
+            partition::partition_entries(&client).await?;
+        }
+
+        if self.truncate {
+            match self.layout {
+                Layout::Flat => {
+                    client.execute("TRUNCATE TABLE bridge_entry, bridge_file", &[])
+                        .await
+                        .map_err(|e| BridgeError::Database(format!("Failed to truncate tables: {}", e)))?;
+                }
+                Layout::Normalized => pg_norm::truncate(&client).await?,
+            }
+        }
+
+        // Once partitioned, every export creates the months it needs
+        let partitioned = self.layout == Layout::Flat && partition::is_partitioned(&client).await?;
+
+        Ok(PostgresWriter {
+            config: self,
+            client,
+            partitioned,
+            months: HashSet::new(),
+            seen: HashSet::new(),
+            pending: Vec::new(),
+            pending_rows: 0,
+            stats: LoadStats::default(),
+            started,
+        })
     }
+}
 
-    for file in items {
-        insert_file(&tx, &file).await?;
-        insert_lines(&tx, &file.file_sha, &file.lines, file.published).await?;
+impl AssignmentWriter for PostgresWriter<'_> {
+    async fn write_assignment(&mut self, file: &BridgeParsedAssignment) -> Result<(), BridgeError> {
+        if !self.seen.insert(file.file_sha.clone()) {
+            return Ok(());
+        }
+        self.pending_rows += file.lines.len();
+        self.pending.push(file.clone());
+        if self.pending_rows >= self.config.batch_size.max(1) {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn finish(self) -> Result<(), BridgeError> {
+        self.finish_load().await.map(|_| ())
+    }
+
+    async fn abort(self) -> Result<(), BridgeError> {
+        rollback(&self.client).await
+    }
+}
+
+impl PostgresWriter<'_> {
+    /// Merge the pending files: one lookup of those already loaded, then
+    /// their entries in `batch_size` batches.
+    async fn flush(&mut self) -> Result<(), BridgeError> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let mut items = std::mem::take(&mut self.pending);
+        self.pending_rows = 0;
+        let config = self.config;
+
+        let existing = existing_files(&self.client, config.layout, items.iter().map(|f| f.file_sha.as_str())).await?;
+        if !existing.is_empty() {
+            match config.mode {
+                ExportMode::SkipExisting => {
+                    let stats = &mut self.stats;
+                    items.retain(|file| {
+                        let loaded = existing.contains(&file.file_sha);
+                        if loaded {
+                            stats.files_skipped += 1;
+                            stats.rows_skipped += file.lines.len() as u64;
+                        }
+                        !loaded
+                    });
+                }
+                ExportMode::ReplaceFile => {
+                    let shas: Vec<&str> = existing.iter().map(String::as_str).collect();
+                    self.stats.rows_deleted += self
+                        .client
+                        .execute(
+                            &format!("DELETE FROM {} WHERE file_sha = ANY($1)", config.layout.entry_table()),
+                            &[&shas],
+                        )
+                        .await
+                        .map_err(|e| BridgeError::Database(format!("Deleting replaced entries failed: {}", e)))?;
+                }
+                ExportMode::Append | ExportMode::Upsert => {}
+            }
+        }
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        match config.layout {
+            Layout::Flat => {
+                if self.partitioned {
+                    let mut months = Vec::new();
+                    for file in &items {
+                        let published = to_naive_utc(file.published)?.date();
+                        if self.months.insert((published.year(), published.month())) {
+                            months.push(published);
+                        }
+                    }
+                    if !months.is_empty() {
+                        partition::ensure_partitions(&self.client, months).await?;
+                    }
+                }
+                load_flat(&self.client, &items, config, entry_key(self.partitioned), &mut self.stats).await
+            }
+            Layout::Normalized => {
+                pg_norm::load(&self.client, &items, config.batch_size, config.mode, &mut self.stats).await
+            }
+        }
+    }
+
+    /// Merge the remaining files, commit the load, refresh the summary views
+    /// and return its row counts.
+    pub async fn finish_load(mut self) -> Result<LoadStats, BridgeError> {
+        self.flush().await?;
+        let config = self.config;
+        let stats = &mut self.stats;
+        stats.rows_skipped += stats.rows_sent - stats.rows_inserted - stats.rows_updated;
+        pg_views::create(&self.client, config.layout, &config.views).await?;
+        commit(&self.client).await?;
+
+        let refresh_started = Instant::now();
+        pg_views::refresh(&self.client, config.layout, &config.views).await?;
+        stats.refresh_time = refresh_started.elapsed();
+
+        info!(
+            batches = stats.batches,
+            rows_sent = stats.rows_sent,
+            rows_inserted = stats.rows_inserted,
+            rows_updated = stats.rows_updated,
+            rows_skipped = stats.rows_skipped,
+            rows_deleted = stats.rows_deleted,
+            files_skipped = stats.files_skipped,
+            copy_ms = stats.copy_time.as_millis() as u64,
+            merge_ms = stats.merge_time.as_millis() as u64,
+            refresh_ms = stats.refresh_time.as_millis() as u64,
+            total_ms = self.started.elapsed().as_millis() as u64,
+            "Loaded {} files into PostgreSQL: {} inserted, {} updated, {} skipped",
+            self.seen.len() - stats.files_skipped,
+            stats.rows_inserted,
+            stats.rows_updated,
+            stats.rows_skipped
+        );
+
+        Ok(self.stats)
     }
+}
 
-    tx.commit()
+/// The subset of `shas` already present in the layout's file table.
+pub(crate) async fn existing_files<'a>(
+    tx: &Client,
+    layout: Layout,
+    shas: impl Iterator<Item = &'a str>,
+) -> Result<HashSet<String>, BridgeError> {
+    let shas: Vec<&str> = shas.collect();
+    let rows = tx
+        .query(&format!("SELECT sha FROM {} WHERE sha = ANY($1)", layout.file_table()), &[&shas])
         .await
-        .map_err(|e| BridgeError::Database(format!("Commit failed: {}", e)))?;
+        .map_err(|e| BridgeError::Database(format!("Looking up loaded files failed: {}", e)))?;
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
 
-    Ok(())
+/// Load into the flat `bridge_file` / `bridge_entry` tables.
+async fn load_flat(
+    tx: &Client,
+    items: &[BridgeParsedAssignment],
+    config: &PostgresExporter,
+    key: &[&str],
+    stats: &mut LoadStats,
+) -> Result<(), BridgeError> {
+    for file in items {
+        insert_file(tx, file, config.mode).await?;
+    }
+
+    match config.load_mode {
+        LoadMode::Copy => copy_lines(tx, items, config.batch_size, key, config.mode, stats).await,
+        LoadMode::Insert => {
+            let insert_started = Instant::now();
+            for file in items {
+                insert_lines(tx, &file.file_sha, &file.lines, file.published, key, config.mode, stats).await?;
+            }
+            stats.batches += items.len();
+            stats.merge_time += insert_started.elapsed();
+            Ok(())
+        }
+    }
 }
 
-/// Ensure both bridge_file and bridge_entry tables exist.
-async fn prepare_schema(tx: &Transaction<'_>) -> Result<(), BridgeError> {
-    tx.execute(
-        "CREATE TABLE IF NOT EXISTS bridge_file (
-            sha TEXT PRIMARY KEY,
-            header TEXT NOT NULL,
-            published TIMESTAMP NOT NULL
-        )",
-        &[],
+/// Bulk-load all entries via binary COPY into a temporary staging table, merging
+/// each batch into bridge_entry according to `mode`.
+async fn copy_lines(
+    tx: &Client,
+    items: &[BridgeParsedAssignment],
+    batch_size: usize,
+    key: &[&str],
+    mode: ExportMode,
+    stats: &mut LoadStats,
+) -> Result<(), BridgeError> {
+    tx.batch_execute(
+        "CREATE TEMP TABLE IF NOT EXISTS bridge_entry_staging
+            (LIKE bridge_entry INCLUDING DEFAULTS) ON COMMIT DROP",
     )
     .await
-    .map_err(|e| BridgeError::Database(format!("Creating bridge_file failed: {}", e)))?;
+    .map_err(|e| BridgeError::Database(format!("Creating staging table failed: {}", e)))?;
 
-    tx.execute(
-        "CREATE TABLE IF NOT EXISTS bridge_entry (
-            sha TEXT PRIMARY KEY,
-            fingerprint TEXT NOT NULL,
-            method TEXT NOT NULL,
-            file_sha TEXT REFERENCES bridge_file(sha),
-            transport TEXT,
-            ip TEXT,
-            block TEXT,
-            distributed BOOLEAN,
-            state TEXT,
-            bandwidth TEXT,
-            ratio REAL,
-            published TIMESTAMP NOT NULL
-        )",
-        &[],
-    )
-    .await
-    .map_err(|e| BridgeError::Database(format!("Creating bridge_entry failed: {}", e)))?;
+    let mut rows = Vec::new();
+    for file in items {
+        let published = to_naive_utc(file.published)?;
+        rows.extend(file.lines.iter().map(|entry| (file.file_sha.as_str(), published, entry)));
+    }
+
+    let merge = merge_statement(
+        "bridge_entry",
+        &ENTRY_COLUMNS,
+        key,
+        &format!("SELECT DISTINCT ON (sha) {} FROM bridge_entry_staging", ENTRY_COLUMNS.join(", ")),
+        mode,
+    );
+    for batch in rows.chunks(batch_size.max(1)) {
+        copy_batch(tx, batch, &merge, stats).await?;
+    }
 
     Ok(())
 }
 
+/// COPY one batch into the staging table, then merge and clear it.
+async fn copy_batch(
+    tx: &Client,
+    batch: &[(&str, NaiveDateTime, &BridgeLineEntry)],
+    merge: &str,
+    stats: &mut LoadStats,
+) -> Result<(), BridgeError> {
+    let copy_started = Instant::now();
+    let sink = tx
+        .copy_in(
+            "COPY bridge_entry_staging (
+                sha, fingerprint, method, file_sha,
+                transport, ip, block, distributed,
+                state, bandwidth, ratio, published
+            ) FROM STDIN (FORMAT binary)",
+        )
+        .await
+        .map_err(|e| BridgeError::Database(format!("Starting COPY failed: {}", e)))?;
+
+    let types = [
+        Type::TEXT, Type::TEXT, Type::TEXT, Type::TEXT,
+        Type::TEXT, Type::TEXT, Type::TEXT, Type::BOOL,
+        Type::TEXT, Type::TEXT, Type::FLOAT4, Type::TIMESTAMP,
+    ];
+    let mut writer = pin!(BinaryCopyInWriter::new(sink, &types));
+    for (file_sha, published, entry) in batch {
+        writer
+            .as_mut()
+            .write(&[
+                &entry.sha,
+                &entry.fingerprint,
+                &entry.distribution_method,
+                file_sha,
+                &entry.transport,
+                &entry.ip,
+                &entry.blocklist,
+                &entry.distributed,
+                &entry.state,
+                &entry.bandwidth,
+                &entry.ratio,
+                published,
+            ])
+            .await
+            .map_err(|e| BridgeError::Database(format!("COPY into staging failed: {}", e)))?;
+    }
+    let sent = writer
+        .finish()
+        .await
+        .map_err(|e| BridgeError::Database(format!("Finishing COPY failed: {}", e)))?;
+    let copy_time = copy_started.elapsed();
+
+    let merge_started = Instant::now();
+    let (inserted, updated) = run_merge(tx, merge, &[]).await?;
+    tx.execute("TRUNCATE bridge_entry_staging", &[])
+        .await
+        .map_err(|e| BridgeError::Database(format!("Clearing staging table failed: {}", e)))?;
+    let merge_time = merge_started.elapsed();
+
+    stats.batches += 1;
+    stats.rows_sent += sent;
+    stats.rows_inserted += inserted;
+    stats.rows_updated += updated;
+    stats.copy_time += copy_time;
+    stats.merge_time += merge_time;
+    debug!(
+        batch = stats.batches,
+        rows = sent,
+        inserted,
+        updated,
+        copy_ms = copy_time.as_millis() as u64,
+        merge_ms = merge_time.as_millis() as u64,
+        "Copied batch into bridge_entry"
+    );
+
+    Ok(())
+}
+
+/// Conflict clause for file rows: refreshed whenever entries may be rewritten.
+pub(crate) fn file_conflict(mode: ExportMode) -> &'static str {
+    match mode {
+        ExportMode::Upsert | ExportMode::ReplaceFile => {
+            "ON CONFLICT (sha) DO UPDATE SET header = EXCLUDED.header, published = EXCLUDED.published"
+        }
+        ExportMode::Append | ExportMode::SkipExisting => "ON CONFLICT DO NOTHING",
+    }
+}
+
 /// Insert one bridge_file row
-async fn insert_file(tx: &Transaction<'_>, file: &BridgeParsedAssignment) -> Result<(), BridgeError> {
+async fn insert_file(tx: &Client, file: &BridgeParsedAssignment, mode: ExportMode) -> Result<(), BridgeError> {
     let published = to_naive_utc(file.published)?;
     tx.execute(
-        "INSERT INTO bridge_file (sha, header, published)
-         VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+        &format!(
+            "INSERT INTO bridge_file (sha, header, published) VALUES ($1, $2, $3) {}",
+            file_conflict(mode)
+        ),
         &[&file.file_sha, &file.header, &published],
     )
     .await
@@ -95,50 +649,48 @@ async fn insert_file(tx: &Transaction<'_>, file: &BridgeParsedAssignment) -> Res
     Ok(())
 }
 
-/// Insert multiple bridge_entry rows
+/// Insert multiple bridge_entry rows one statement at a time.
 async fn insert_lines(
-    tx: &Transaction<'_>,
+    tx: &Client,
     file_sha: &str,
     lines: &[BridgeLineEntry],
     millis: i64,
+    key: &[&str],
+    mode: ExportMode,
+    stats: &mut LoadStats,
 ) -> Result<(), BridgeError> {
     let published = to_naive_utc(millis)?;
+    let statement = merge_statement(
+        "bridge_entry",
+        &ENTRY_COLUMNS,
+        key,
+        "VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12)",
+        mode,
+    );
 
     for entry in lines {
-        let method = entry.distribution_method.clone();
-        let transport = entry.transport.clone();
-        let ip = entry.ip.clone();
-        let block = entry.blocklist.clone();
-        let distributed = entry.distributed;
-        let state = entry.state.clone();
-        let bandwidth = entry.bandwidth.clone();
-        let ratio = entry.ratio;
-
-        tx.execute(
-            "INSERT INTO bridge_entry (
-                sha, fingerprint, method, file_sha,
-                transport, ip, block, distributed,
-                state, bandwidth, ratio, published
-            ) VALUES (
-                $1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12
-            ) ON CONFLICT DO NOTHING",
+        let (inserted, updated) = run_merge(
+            tx,
+            &statement,
             &[
                 &entry.sha,
                 &entry.fingerprint,
-                &method,
+                &entry.distribution_method,
                 &file_sha,
-                &transport,
-                &ip,
-                &block,
-                &distributed,
-                &state,
-                &bandwidth,
-                &ratio,
+                &entry.transport,
+                &entry.ip,
+                &entry.blocklist,
+                &entry.distributed,
+                &entry.state,
+                &entry.bandwidth,
+                &entry.ratio,
                 &published,
             ],
         )
-        .await
-        .map_err(|e| BridgeError::Database(format!("Insert into bridge_entry failed: {}", e)))?;
+        .await?;
+        stats.rows_sent += 1;
+        stats.rows_inserted += inserted;
+        stats.rows_updated += updated;
     }
 
     Ok(())
@@ -156,23 +708,107 @@ fn to_naive_utc(ms: i64) -> Result<NaiveDateTime, BridgeError> {
     Ok(utc.naive_utc())
 }
 
+/// Default number of entries per COPY batch.
+pub const DEFAULT_BATCH_SIZE: usize = 10_000;
+
 /// Struct wrapping config info for PostgreSQL export.
 pub struct PostgresExporter {
     pub conn_str: String,
     pub truncate: bool,
+    pub load_mode: LoadMode,
+    /// Number of entries per COPY batch.
+    pub batch_size: usize,
+    pub tls: TlsConfig,
+    pub layout: Layout,
+    pub mode: ExportMode,
+    /// Partition bridge_entry by month on `published`, converting it on the next export.
+    pub partition: bool,
+    /// Materialised summary views to create and refresh after each load.
+    pub views: Vec<SummaryView>,
 }
 
-/// Implements `Exporter` trait for PostgreSQL backend.
-impl Exporter for PostgresExporter {
-    fn export(&self, data: &[BridgeParsedAssignment]) -> Result<(), BridgeError> {
-        let items = data.to_vec();
-        let conn = self.conn_str.clone();
-        let truncate = self.truncate;
+impl PostgresExporter {
+    pub fn new(conn_str: String) -> Self {
+        PostgresExporter {
+            conn_str,
+            truncate: false,
+            load_mode: LoadMode::default(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            tls: TlsConfig::default(),
+            layout: Layout::default(),
+            mode: ExportMode::default(),
+            partition: false,
+            views: Vec::new(),
+        }
+    }
+
+    /// Export and return the row counts of the load.
+    pub fn load(&self, data: &[BridgeParsedAssignment]) -> Result<LoadStats, BridgeError> {
+        block_on(async {
+            let mut writer = self.begin().await?;
+            for file in data {
+                if let Err(e) = writer.write_assignment(file).await {
+                    abort_after(writer, &e).await;
+                    return Err(e);
+                }
+            }
+            writer.finish_load().await
+        })
+    }
+
+    /// The subset of `shas` whose files are already loaded, so they can be
+    /// dropped before parsing with `ExportMode::SkipExisting`.
+    pub fn loaded_files(&self, shas: &[String]) -> Result<HashSet<String>, BridgeError> {
+        block_on(async {
+            let client = connect(&self.conn_str, &self.tls).await?;
+            begin(&client).await?;
+            migrate_up(&client, false).await?;
+            let loaded = existing_files(&client, self.layout, shas.iter().map(String::as_str)).await?;
+            commit(&client).await?;
+            Ok(loaded)
+        })
+    }
+
+    /// Apply pending schema migrations, or with `dry_run` only list them.
+    pub fn migrate(&self, dry_run: bool) -> Result<Vec<Migration>, BridgeError> {
+        block_on(async {
+            let client = connect(&self.conn_str, &self.tls).await?;
+            begin(&client).await?;
+            let pending = migrate_up(&client, dry_run).await?;
+            if dry_run {
+                rollback(&client).await?;
+            } else {
+                commit(&client).await?;
+            }
+            Ok(pending)
+        })
+    }
+
+    /// Detach, drop or archive the monthly partitions that end on or before
+    /// `cutoff`; with `dry_run` they are only listed.
+    pub fn prune(&self, cutoff: NaiveDate, action: &PruneAction, dry_run: bool) -> Result<Vec<Partition>, BridgeError> {
+        block_on(async {
+            let client = connect(&self.conn_str, &self.tls).await?;
+            begin(&client).await?;
+            migrate_up(&client, false).await?;
+            let pruned = partition::prune(&client, cutoff, action, dry_run).await?;
+            if dry_run {
+                rollback(&client).await?;
+            } else {
+                commit(&client).await?;
+            }
+            Ok(pruned)
+        })
+    }
 
-        tokio::runtime::Runtime::new()
-            .map_err(|e| BridgeError::Database(format!("Tokio runtime init failed: {}", e)))?
-            .block_on(async move {
-                write_to_postgres(items, &conn, truncate).await
-            })
+    /// List known and applied schema migrations.
+    pub fn migration_status(&self) -> Result<Vec<MigrationStatus>, BridgeError> {
+        block_on(async {
+            let client = connect(&self.conn_str, &self.tls).await?;
+            begin(&client).await?;
+            let status = migration_status(&client).await?;
+            commit(&client).await?;
+            Ok(status)
+        })
     }
 }
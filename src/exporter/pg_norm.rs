@@ -0,0 +1,284 @@
+use std::collections::{BTreeSet, HashMap};
+use std::pin::pin;
+use std::time::Instant;
+use chrono::{DateTime, Utc};
+use tokio_postgres::binary_copy::BinaryCopyInWriter;
+use tokio_postgres::types::Type;
+use tokio_postgres::Client;
+use tracing::debug;
+use crate::error::BridgeError;
+use crate::exporter::pg::{file_conflict, merge_statement, run_merge, ExportMode, LoadStats};
+use crate::helper::millis_to_utc;
+use crate::transformer::BridgeParsedAssignment;
+
+/// Lookup tables in the `bridge_norm` schema, each `(id SERIAL, name TEXT UNIQUE)`.
+const METHOD: &str = "distribution_method";
+const TRANSPORT: &str = "transport";
+const STATE: &str = "state";
+const BANDWIDTH: &str = "bandwidth";
+
+/// Empty the normalised fact and dimension tables; lookup tables are kept.
+pub(crate) async fn truncate(tx: &Client) -> Result<(), BridgeError> {
+    tx.execute(
+        "TRUNCATE TABLE bridge_norm.assignment, bridge_norm.bridge, bridge_norm.file",
+        &[],
+    )
+    .await
+    .map_err(|e| BridgeError::Database(format!("Failed to truncate normalised tables: {}", e)))?;
+    Ok(())
+}
+
+/// Load files, bridges and assignments into the `bridge_norm` schema.
+///
+/// Lookup values and bridges are upserted first so assignments can be copied
+/// with their ids; assignments go through a binary COPY staging table like
+/// the flat layout and are merged according to `mode`.
+pub(crate) async fn load(
+    tx: &Client,
+    items: &[BridgeParsedAssignment],
+    batch_size: usize,
+    mode: ExportMode,
+    stats: &mut LoadStats,
+) -> Result<(), BridgeError> {
+    for file in items {
+        tx.execute(
+            &format!(
+                "INSERT INTO bridge_norm.file (sha, header, published) VALUES ($1, $2, $3) {}",
+                file_conflict(mode)
+            ),
+            &[&file.file_sha, &file.header, &published(file.published)?],
+        )
+        .await
+        .map_err(|e| BridgeError::Database(format!("Insert into bridge_norm.file failed: {}", e)))?;
+    }
+
+    let lines = || items.iter().flat_map(|f| &f.lines);
+    let methods = lookup_ids(tx, METHOD, lines().map(|l| Some(l.distribution_method.as_str()))).await?;
+    let transports = lookup_ids(tx, TRANSPORT, lines().map(|l| l.transport.as_deref())).await?;
+    let states = lookup_ids(tx, STATE, lines().map(|l| l.state.as_deref())).await?;
+    let bandwidths = lookup_ids(tx, BANDWIDTH, lines().map(|l| l.bandwidth.as_deref())).await?;
+    let bridges = upsert_bridges(tx, items, batch_size).await?;
+
+    let mut rows = Vec::new();
+    for file in items {
+        let published = published(file.published)?;
+        for line in &file.lines {
+            rows.push(AssignmentRow {
+                sha: &line.sha,
+                file_sha: &file.file_sha,
+                bridge_id: bridges[line.fingerprint.as_str()],
+                method_id: methods[line.distribution_method.as_str()],
+                transport_id: line.transport.as_deref().map(|t| transports[t]),
+                ip_versions: line.ip.as_ref().map(|_| line.ip_versions().into_iter().map(i16::from).collect()),
+                blocklist: line.blocklist.as_ref().map(|_| line.blocked_countries()),
+                distributed: line.distributed,
+                state_id: line.state.as_deref().map(|s| states[s]),
+                bandwidth_id: line.bandwidth.as_deref().map(|b| bandwidths[b]),
+                ratio: line.ratio,
+                published,
+            });
+        }
+    }
+
+    tx.batch_execute(
+        "CREATE TEMP TABLE IF NOT EXISTS bridge_norm_assignment_staging
+            (LIKE bridge_norm.assignment INCLUDING DEFAULTS) ON COMMIT DROP",
+    )
+    .await
+    .map_err(|e| BridgeError::Database(format!("Creating staging table failed: {}", e)))?;
+
+    let merge = merge_statement(
+        "bridge_norm.assignment",
+        &ASSIGNMENT_COLUMNS,
+        &["sha"],
+        &format!(
+            "SELECT DISTINCT ON (sha) {} FROM bridge_norm_assignment_staging",
+            ASSIGNMENT_COLUMNS.join(", ")
+        ),
+        mode,
+    );
+    for batch in rows.chunks(batch_size.max(1)) {
+        copy_batch(tx, batch, &merge, stats).await?;
+    }
+
+    Ok(())
+}
+
+/// Columns of bridge_norm.assignment, in COPY order.
+const ASSIGNMENT_COLUMNS: [&str; 12] = [
+    "sha", "file_sha", "bridge_id", "method_id",
+    "transport_id", "ip_versions", "blocklist", "distributed",
+    "state_id", "bandwidth_id", "ratio", "published",
+];
+
+/// One `bridge_norm.assignment` row with resolved ids.
+struct AssignmentRow<'a> {
+    sha: &'a str,
+    file_sha: &'a str,
+    bridge_id: i64,
+    method_id: i32,
+    transport_id: Option<i32>,
+    ip_versions: Option<Vec<i16>>,
+    blocklist: Option<Vec<String>>,
+    distributed: Option<bool>,
+    state_id: Option<i32>,
+    bandwidth_id: Option<i32>,
+    ratio: Option<f32>,
+    published: DateTime<Utc>,
+}
+
+fn published(millis: i64) -> Result<DateTime<Utc>, BridgeError> {
+    millis_to_utc(millis).ok_or_else(|| BridgeError::InvalidTimestamp(millis.to_string()))
+}
+
+/// Insert missing names into a lookup table and return the ids of all given names.
+/// Only names not in the table yet are inserted, as every attempted insert uses
+/// up a sequence value even when it conflicts.
+async fn lookup_ids<'a>(
+    tx: &Client,
+    table: &str,
+    names: impl Iterator<Item = Option<&'a str>>,
+) -> Result<HashMap<String, i32>, BridgeError> {
+    let names: Vec<&str> = names.flatten().collect::<BTreeSet<_>>().into_iter().collect();
+
+    tx.execute(
+        &format!(
+            "INSERT INTO bridge_norm.{table} (name)
+             SELECT n FROM unnest($1::text[]) n
+             WHERE NOT EXISTS (SELECT 1 FROM bridge_norm.{table} WHERE name = n)
+             ON CONFLICT (name) DO NOTHING"
+        ),
+        &[&names],
+    )
+    .await
+    .map_err(|e| BridgeError::Database(format!("Insert into bridge_norm.{} failed: {}", table, e)))?;
+
+    let rows = tx
+        .query(
+            &format!("SELECT name, id FROM bridge_norm.{} WHERE name = ANY($1)", table),
+            &[&names],
+        )
+        .await
+        .map_err(|e| BridgeError::Database(format!("Reading bridge_norm.{} failed: {}", table, e)))?;
+
+    Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+}
+
+/// Upsert the `bridge` dimension, widening first/last seen, and return ids by fingerprint.
+async fn upsert_bridges(
+    tx: &Client,
+    items: &[BridgeParsedAssignment],
+    batch_size: usize,
+) -> Result<HashMap<String, i64>, BridgeError> {
+    let mut seen: HashMap<&str, (i64, i64)> = HashMap::new();
+    for file in items {
+        for line in &file.lines {
+            let span = seen
+                .entry(line.fingerprint.as_str())
+                .or_insert((file.published, file.published));
+            span.0 = span.0.min(file.published);
+            span.1 = span.1.max(file.published);
+        }
+    }
+
+    let seen: Vec<_> = seen.into_iter().collect();
+    let mut ids = HashMap::with_capacity(seen.len());
+    for chunk in seen.chunks(batch_size.max(1)) {
+        let fingerprints: Vec<&str> = chunk.iter().map(|(f, _)| *f).collect();
+        let first_seen = chunk.iter().map(|(_, (first, _))| published(*first)).collect::<Result<Vec<_>, _>>()?;
+        let last_seen = chunk.iter().map(|(_, (_, last))| published(*last)).collect::<Result<Vec<_>, _>>()?;
+
+        let rows = tx
+            .query(
+                "INSERT INTO bridge_norm.bridge (fingerprint, first_seen, last_seen)
+                 SELECT * FROM unnest($1::text[], $2::timestamptz[], $3::timestamptz[])
+                 ON CONFLICT (fingerprint) DO UPDATE SET
+                     first_seen = LEAST(bridge.first_seen, EXCLUDED.first_seen),
+                     last_seen = GREATEST(bridge.last_seen, EXCLUDED.last_seen)
+                 RETURNING fingerprint, id",
+                &[&fingerprints, &first_seen, &last_seen],
+            )
+            .await
+            .map_err(|e| BridgeError::Database(format!("Upsert into bridge_norm.bridge failed: {}", e)))?;
+        ids.extend(rows.iter().map(|row| (row.get(0), row.get(1))));
+    }
+
+    Ok(ids)
+}
+
+/// COPY one batch of assignments into the staging table, then merge and clear it.
+async fn copy_batch(
+    tx: &Client,
+    batch: &[AssignmentRow<'_>],
+    merge: &str,
+    stats: &mut LoadStats,
+) -> Result<(), BridgeError> {
+    let copy_started = Instant::now();
+    let sink = tx
+        .copy_in(
+            "COPY bridge_norm_assignment_staging (
+                sha, file_sha, bridge_id, method_id,
+                transport_id, ip_versions, blocklist, distributed,
+                state_id, bandwidth_id, ratio, published
+            ) FROM STDIN (FORMAT binary)",
+        )
+        .await
+        .map_err(|e| BridgeError::Database(format!("Starting COPY failed: {}", e)))?;
+
+    let types = [
+        Type::TEXT, Type::TEXT, Type::INT8, Type::INT4,
+        Type::INT4, Type::INT2_ARRAY, Type::TEXT_ARRAY, Type::BOOL,
+        Type::INT4, Type::INT4, Type::FLOAT4, Type::TIMESTAMPTZ,
+    ];
+    let mut writer = pin!(BinaryCopyInWriter::new(sink, &types));
+    for row in batch {
+        writer
+            .as_mut()
+            .write(&[
+                &row.sha,
+                &row.file_sha,
+                &row.bridge_id,
+                &row.method_id,
+                &row.transport_id,
+                &row.ip_versions,
+                &row.blocklist,
+                &row.distributed,
+                &row.state_id,
+                &row.bandwidth_id,
+                &row.ratio,
+                &row.published,
+            ])
+            .await
+            .map_err(|e| BridgeError::Database(format!("COPY into staging failed: {}", e)))?;
+    }
+    let sent = writer
+        .finish()
+        .await
+        .map_err(|e| BridgeError::Database(format!("Finishing COPY failed: {}", e)))?;
+    let copy_time = copy_started.elapsed();
+
+    let merge_started = Instant::now();
+    let (inserted, updated) = run_merge(tx, merge, &[]).await?;
+    tx.execute("TRUNCATE bridge_norm_assignment_staging", &[])
+        .await
+        .map_err(|e| BridgeError::Database(format!("Clearing staging table failed: {}", e)))?;
+    let merge_time = merge_started.elapsed();
+
+    stats.batches += 1;
+    stats.rows_sent += sent;
+    stats.rows_inserted += inserted;
+    stats.rows_updated += updated;
+    stats.copy_time += copy_time;
+    stats.merge_time += merge_time;
+    debug!(
+        batch = stats.batches,
+        rows = sent,
+        inserted,
+        updated,
+        copy_ms = copy_time.as_millis() as u64,
+        merge_ms = merge_time.as_millis() as u64,
+        "Copied batch into bridge_norm.assignment"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use std::str::FromStr;
+use tokio_postgres::Client;
+use tracing::debug;
+use crate::error::BridgeError;
+use crate::exporter::pg::Layout;
+
+/// Materialised summary views the PostgreSQL exporter can maintain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SummaryView {
+    /// Entries and distinct bridges per day, distributor, transport and state.
+    Daily,
+    /// Bridges per distributor, transport and state in the latest file.
+    Pool,
+    /// First and last publication time of every fingerprint.
+    Seen,
+}
+
+impl SummaryView {
+    pub const ALL: [SummaryView; 3] = [SummaryView::Daily, SummaryView::Pool, SummaryView::Seen];
+
+    fn name(&self) -> &'static str {
+        match self {
+            SummaryView::Daily => "bridge_daily_counts",
+            SummaryView::Pool => "bridge_pool_composition",
+            SummaryView::Seen => "bridge_fingerprint_seen",
+        }
+    }
+
+    /// Qualified name; the normalised layout keeps its views in `bridge_norm`.
+    pub(crate) fn qualified_name(&self, layout: Layout) -> String {
+        match layout {
+            Layout::Flat => self.name().to_string(),
+            Layout::Normalized => format!("bridge_norm.{}", self.name()),
+        }
+    }
+
+    /// Query over the flat-shaped tables of `layout`, and the columns of the
+    /// unique index that `REFRESH ... CONCURRENTLY` needs.
+    fn definition(&self, layout: Layout) -> (String, &'static str) {
+        let (entry, file) = match layout {
+            Layout::Flat => ("bridge_entry", "bridge_file"),
+            Layout::Normalized => ("bridge_norm.bridge_entry", "bridge_norm.bridge_file"),
+        };
+        match self {
+            SummaryView::Daily => (
+                format!(
+                    "SELECT published::date AS day, method, transport, state,
+                            count(*) AS entries, count(DISTINCT fingerprint) AS bridges
+                     FROM {entry} GROUP BY 1, 2, 3, 4"
+                ),
+                "day, method, transport, state",
+            ),
+            SummaryView::Pool => (
+                format!(
+                    "SELECT f.published, e.method, e.transport, e.state, count(*) AS bridges
+                     FROM {entry} e
+                     JOIN (SELECT sha, published FROM {file} ORDER BY published DESC, sha LIMIT 1) f
+                       ON f.sha = e.file_sha
+                     GROUP BY 1, 2, 3, 4"
+                ),
+                "method, transport, state",
+            ),
+            SummaryView::Seen => (
+                format!(
+                    "SELECT fingerprint, min(published) AS first_seen, max(published) AS last_seen,
+                            count(DISTINCT file_sha) AS files
+                     FROM {entry} GROUP BY fingerprint"
+                ),
+                "fingerprint",
+            ),
+        }
+    }
+}
+
+impl FromStr for SummaryView {
+    type Err = BridgeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "daily" => Ok(SummaryView::Daily),
+            "pool" => Ok(SummaryView::Pool),
+            "seen" => Ok(SummaryView::Seen),
+            other => Err(BridgeError::Config(format!(
+                "Unknown summary view '{}', expected daily, pool or seen",
+                other
+            ))),
+        }
+    }
+}
+
+/// Create the missing `views`, populated from the current transaction's data.
+pub(crate) async fn create(tx: &Client, layout: Layout, views: &[SummaryView]) -> Result<(), BridgeError> {
+    for view in views {
+        let name = view.qualified_name(layout);
+        let (query, key) = view.definition(layout);
+        let index = format!("{}_key", view.name());
+        tx.batch_execute(&format!(
+            "CREATE MATERIALIZED VIEW IF NOT EXISTS {name} AS {query};
+             CREATE UNIQUE INDEX IF NOT EXISTS {index} ON {name} ({key});"
+        ))
+        .await
+        .map_err(|e| BridgeError::Database(format!("Creating materialised view {} failed: {}", name, e)))?;
+    }
+    Ok(())
+}
+
+/// Drop every summary view of the flat layout, so `bridge_entry` can be replaced.
+pub(crate) async fn drop_flat(tx: &Client) -> Result<(), BridgeError> {
+    let names: Vec<String> = SummaryView::ALL.iter().map(|v| v.qualified_name(Layout::Flat)).collect();
+    tx.batch_execute(&format!("DROP MATERIALIZED VIEW IF EXISTS {}", names.join(", ")))
+        .await
+        .map_err(|e| BridgeError::Database(format!("Dropping materialised views failed: {}", e)))
+}
+
+/// Refresh `views` without blocking readers; runs outside the load transaction.
+pub(crate) async fn refresh(client: &Client, layout: Layout, views: &[SummaryView]) -> Result<(), BridgeError> {
+    for view in views {
+        let name = view.qualified_name(layout);
+        client
+            .batch_execute(&format!("REFRESH MATERIALIZED VIEW CONCURRENTLY {}", name))
+            .await
+            .map_err(|e| {
+                BridgeError::Database(format!(
+                    "Refreshing materialised view {} failed (the data itself was committed): {}",
+                    name, e
+                ))
+            })?;
+        debug!("Refreshed materialised view {}", name);
+    }
+    Ok(())
+}
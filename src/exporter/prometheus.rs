@@ -0,0 +1,150 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::PathBuf;
+use tracing::{info, warn};
+use crate::analysis::transport_key;
+use crate::error::BridgeError;
+use crate::exporter::output::AtomicFile;
+use crate::exporter::{AssignmentWriter, IfExists, StreamingExporter};
+use crate::transformer::BridgeParsedAssignment;
+
+/// Writes the composition of the latest assignment as Prometheus exposition
+/// format, for node_exporter's textfile collector. The file is replaced
+/// atomically, so the collector never reads half of it.
+pub struct PrometheusExporter {
+    /// Output file; the textfile collector only reads `*.prom` files.
+    pub output_path: PathBuf,
+}
+
+impl PrometheusExporter {
+    pub fn new(output_path: PathBuf) -> Self {
+        PrometheusExporter { output_path }
+    }
+}
+
+/// `method`, `transport`, `state`, `ip` labels of one pool gauge.
+type PoolKey = (String, String, String, String);
+
+/// Metrics of one assignment file.
+struct Composition {
+    file_sha: String,
+    published: i64,
+    bridges: BTreeMap<PoolKey, u64>,
+    /// Country code -> bridges blocked there.
+    blocked: BTreeMap<String, u64>,
+}
+
+impl Composition {
+    fn of(file: &BridgeParsedAssignment) -> Self {
+        let mut bridges = BTreeMap::new();
+        let mut blocked = BTreeMap::new();
+        for line in &file.lines {
+            let ip: Vec<String> = line.ip_versions().iter().map(u8::to_string).collect();
+            let key = (
+                line.distribution_method.clone(),
+                transport_key(line.transport.as_deref()),
+                line.state.clone().unwrap_or_else(|| "unknown".to_string()),
+                if ip.is_empty() { "unknown".to_string() } else { ip.join(",") },
+            );
+            *bridges.entry(key).or_default() += 1;
+            for country in line.blocked_countries().into_iter().collect::<BTreeSet<_>>() {
+                *blocked.entry(country).or_default() += 1;
+            }
+        }
+        Composition { file_sha: file.file_sha.clone(), published: file.published, bridges, blocked }
+    }
+
+    /// Whether `file` is later than this one; ties go to the lower `file_sha`,
+    /// like the `pool` summary view.
+    fn is_superseded_by(&self, file: &BridgeParsedAssignment) -> bool {
+        (file.published, &self.file_sha) > (self.published, &file.file_sha)
+    }
+
+    /// Render every metric, sorted by labels so that unchanged pools give identical files.
+    fn render(&self) -> String {
+        let mut out = String::new();
+        header(
+            &mut out,
+            "bridge_pool_bridges",
+            "Bridges in the latest assignment by distribution method, transport, state and IP versions.",
+        );
+        for ((method, transport, state, ip), count) in &self.bridges {
+            let _ = writeln!(
+                out,
+                "bridge_pool_bridges{{method=\"{}\",transport=\"{}\",state=\"{}\",ip=\"{}\"}} {}",
+                escape(method),
+                escape(transport),
+                escape(state),
+                escape(ip),
+                count
+            );
+        }
+        header(&mut out, "bridge_pool_blocked_bridges", "Bridges in the latest assignment blocked in a country.");
+        for (country, count) in &self.blocked {
+            let _ = writeln!(out, "bridge_pool_blocked_bridges{{country=\"{}\"}} {}", escape(country), count);
+        }
+        header(&mut out, "bridge_pool_published_timestamp_seconds", "Publication time of the latest assignment.");
+        let _ = writeln!(out, "bridge_pool_published_timestamp_seconds {}", self.published as f64 / 1000.0);
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+}
+
+/// Escape a label value for the exposition format.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Open output of a [`PrometheusExporter`]; only the latest file seen is kept.
+pub struct PrometheusWriter<'a> {
+    exporter: &'a PrometheusExporter,
+    latest: Option<Composition>,
+}
+
+impl StreamingExporter for PrometheusExporter {
+    type Writer<'a> = PrometheusWriter<'a>;
+
+    async fn begin(&self) -> Result<PrometheusWriter<'_>, BridgeError> {
+        Ok(PrometheusWriter { exporter: self, latest: None })
+    }
+}
+
+impl AssignmentWriter for PrometheusWriter<'_> {
+    async fn write_assignment(&mut self, assignment: &BridgeParsedAssignment) -> Result<(), BridgeError> {
+        if self.latest.as_ref().is_none_or(|latest| latest.is_superseded_by(assignment)) {
+            self.latest = Some(Composition::of(assignment));
+        }
+        Ok(())
+    }
+
+    /// Replace the output; without any assignment it is left as it was, so
+    /// its timestamp goes stale rather than the metrics disappearing.
+    async fn finish(self) -> Result<(), BridgeError> {
+        let latest = match self.latest {
+            Some(latest) => latest,
+            None => {
+                warn!("⚠️ No assignments, leaving {} unchanged", self.exporter.output_path.display());
+                return Ok(());
+            }
+        };
+        let mut file = AtomicFile::create(&self.exporter.output_path, IfExists::Overwrite)?;
+        file.write_all(latest.render().as_bytes())?;
+        file.commit()?;
+        info!(
+            " Wrote pool composition of {} ({} bridges) to {}",
+            latest.file_sha,
+            latest.bridges.values().sum::<u64>(),
+            self.exporter.output_path.display()
+        );
+        Ok(())
+    }
+
+    async fn abort(self) -> Result<(), BridgeError> {
+        Ok(())
+    }
+}
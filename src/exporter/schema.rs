@@ -0,0 +1,240 @@
+use std::str::FromStr;
+use chrono::SecondsFormat;
+use crate::error::BridgeError;
+use crate::helper::millis_to_utc;
+use crate::transformer::{BridgeLineEntry, BridgeParsedAssignment};
+
+/// A column that row-oriented exporters (CSV, Parquet) can write per bridge entry.
+///
+/// File-level columns (`file_sha`, `published_timestamp`, `header`) are repeated
+/// on every row of the file they belong to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    FileSha,
+    Published,
+    Header,
+    EntrySha,
+    Fingerprint,
+    DistributionMethod,
+    Transport,
+    Ip,
+    Blocklist,
+    Distributed,
+    State,
+    Bandwidth,
+    Ratio,
+}
+
+/// The value type of a column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnKind {
+    Text,
+    Timestamp,
+    Bool,
+    Float,
+}
+
+/// The value of one column for one row, borrowed from the parsed data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnValue<'a> {
+    Text(Option<&'a str>),
+    Timestamp(i64),
+    Bool(Option<bool>),
+    Float(Option<f32>),
+}
+
+impl Column {
+    /// Every column, in the default export order. `header` is opt-in.
+    pub const ALL: [Column; 13] = [
+        Column::FileSha,
+        Column::Published,
+        Column::Header,
+        Column::EntrySha,
+        Column::Fingerprint,
+        Column::DistributionMethod,
+        Column::Transport,
+        Column::Ip,
+        Column::Blocklist,
+        Column::Distributed,
+        Column::State,
+        Column::Bandwidth,
+        Column::Ratio,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::FileSha => "file_sha",
+            Column::Published => "published_timestamp",
+            Column::Header => "header",
+            Column::EntrySha => "entry_sha",
+            Column::Fingerprint => "fingerprint",
+            Column::DistributionMethod => "distribution_method",
+            Column::Transport => "transport",
+            Column::Ip => "ip",
+            Column::Blocklist => "blocklist",
+            Column::Distributed => "distributed",
+            Column::State => "state",
+            Column::Bandwidth => "bandwidth",
+            Column::Ratio => "ratio",
+        }
+    }
+
+    pub fn kind(&self) -> ColumnKind {
+        match self {
+            Column::Published => ColumnKind::Timestamp,
+            Column::Distributed => ColumnKind::Bool,
+            Column::Ratio => ColumnKind::Float,
+            _ => ColumnKind::Text,
+        }
+    }
+
+    /// Whether the column repeats few distinct values, so Parquet stores it
+    /// dictionary-encoded.
+    pub fn low_cardinality(&self) -> bool {
+        !matches!(self, Column::EntrySha | Column::Fingerprint | Column::Ratio)
+    }
+
+    /// Whether the column can be empty.
+    pub fn nullable(&self) -> bool {
+        !matches!(
+            self,
+            Column::FileSha
+                | Column::Published
+                | Column::Header
+                | Column::EntrySha
+                | Column::Fingerprint
+                | Column::DistributionMethod
+        )
+    }
+
+    pub fn value<'a>(&self, file: &'a BridgeParsedAssignment, line: &'a BridgeLineEntry) -> ColumnValue<'a> {
+        match self {
+            Column::FileSha => ColumnValue::Text(Some(&file.file_sha)),
+            Column::Published => ColumnValue::Timestamp(file.published),
+            Column::Header => ColumnValue::Text(Some(&file.header)),
+            Column::EntrySha => ColumnValue::Text(Some(&line.sha)),
+            Column::Fingerprint => ColumnValue::Text(Some(&line.fingerprint)),
+            Column::DistributionMethod => ColumnValue::Text(Some(&line.distribution_method)),
+            Column::Transport => ColumnValue::Text(line.transport.as_deref()),
+            Column::Ip => ColumnValue::Text(line.ip.as_deref()),
+            Column::Blocklist => ColumnValue::Text(line.blocklist.as_deref()),
+            Column::Distributed => ColumnValue::Bool(line.distributed),
+            Column::State => ColumnValue::Text(line.state.as_deref()),
+            Column::Bandwidth => ColumnValue::Text(line.bandwidth.as_deref()),
+            Column::Ratio => ColumnValue::Float(line.ratio),
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = BridgeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_ascii_lowercase();
+        let column = match name.as_str() {
+            "published" | "published_timestamp" => Column::Published,
+            "sha" | "entry_sha" => Column::EntrySha,
+            "method" | "distribution_method" => Column::DistributionMethod,
+            "block" | "blocklist" => Column::Blocklist,
+            other => *Column::ALL
+                .iter()
+                .find(|c| c.name() == other)
+                .ok_or_else(|| BridgeError::Config(format!("Unknown column '{}'", s.trim())))?,
+        };
+        Ok(column)
+    }
+}
+
+/// How the file publication time is rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampFormat {
+    /// RFC 3339 / ISO 8601 in UTC, e.g. `2022-04-09T00:29:37Z`.
+    Iso8601,
+    /// Milliseconds since the Unix epoch.
+    #[default]
+    EpochMillis,
+    /// Seconds since the Unix epoch.
+    EpochSeconds,
+}
+
+impl TimestampFormat {
+    pub fn render(&self, millis: i64) -> String {
+        match self {
+            TimestampFormat::Iso8601 => millis_to_utc(millis)
+                .map(|dt| dt.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+                .unwrap_or_default(),
+            TimestampFormat::EpochMillis => millis.to_string(),
+            TimestampFormat::EpochSeconds => millis.div_euclid(1000).to_string(),
+        }
+    }
+}
+
+impl FromStr for TimestampFormat {
+    type Err = BridgeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "iso8601" | "iso" | "rfc3339" => Ok(TimestampFormat::Iso8601),
+            "epoch-ms" | "epoch_ms" | "millis" => Ok(TimestampFormat::EpochMillis),
+            "epoch-s" | "epoch_s" | "epoch" | "seconds" => Ok(TimestampFormat::EpochSeconds),
+            other => Err(BridgeError::Config(format!(
+                "Unknown timestamp format '{}', expected iso8601, epoch-ms or epoch-s",
+                other
+            ))),
+        }
+    }
+}
+
+/// The ordered set of columns written by row-oriented exporters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnSchema {
+    pub columns: Vec<Column>,
+    pub timestamp_format: TimestampFormat,
+}
+
+impl Default for ColumnSchema {
+    /// All columns except `header`, with epoch-millisecond timestamps.
+    fn default() -> Self {
+        ColumnSchema {
+            columns: Column::ALL.into_iter().filter(|c| *c != Column::Header).collect(),
+            timestamp_format: TimestampFormat::default(),
+        }
+    }
+}
+
+impl ColumnSchema {
+    /// Parse a comma-separated column list such as `file_sha,published,fingerprint`.
+    pub fn parse(list: &str, timestamp_format: TimestampFormat) -> Result<Self, BridgeError> {
+        let columns = list
+            .split(',')
+            .filter(|c| !c.trim().is_empty())
+            .map(Column::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if columns.is_empty() {
+            return Err(BridgeError::Config("Column list must not be empty".into()));
+        }
+        if let Some(dup) = columns.iter().enumerate().find(|(i, c)| columns[..*i].contains(c)) {
+            return Err(BridgeError::Config(format!("Column '{}' selected twice", dup.1.name())));
+        }
+
+        Ok(ColumnSchema { columns, timestamp_format })
+    }
+
+    pub fn header(&self) -> Vec<&'static str> {
+        self.columns.iter().map(Column::name).collect()
+    }
+
+    /// Render one row as text, in schema order; missing values become empty strings.
+    pub fn render_row(&self, file: &BridgeParsedAssignment, line: &BridgeLineEntry) -> Vec<String> {
+        self.columns
+            .iter()
+            .map(|c| match c.value(file, line) {
+                ColumnValue::Text(v) => v.unwrap_or_default().to_string(),
+                ColumnValue::Timestamp(ms) => self.timestamp_format.render(ms),
+                ColumnValue::Bool(v) => v.map(|b| b.to_string()).unwrap_or_default(),
+                ColumnValue::Float(v) => v.map(|f| f.to_string()).unwrap_or_default(),
+            })
+            .collect()
+    }
+}
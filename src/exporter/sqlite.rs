@@ -0,0 +1,182 @@
+use std::path::PathBuf;
+use rusqlite::{params, Connection};
+use tracing::info;
+use crate::error::BridgeError;
+use crate::helper::millis_to_utc;
+use crate::transformer::{BridgeLineEntry, BridgeParsedAssignment};
+use crate::exporter::output::PendingPath;
+use crate::exporter::{AssignmentWriter, IfExists, StreamingExporter};
+
+/// Default number of entries written per transaction.
+pub const DEFAULT_BATCH_SIZE: usize = 10_000;
+
+/// Writes assignments into a self-contained SQLite database using the same
+/// `bridge_file`/`bridge_entry` layout as the PostgreSQL exporter.
+pub struct SqliteExporter {
+    pub db_path: PathBuf,
+    /// Number of entries per transaction.
+    pub batch_size: usize,
+    /// [`IfExists::Overwrite`] adds to an existing database in place.
+    pub if_exists: IfExists,
+}
+
+impl SqliteExporter {
+    pub fn new(db_path: PathBuf) -> Self {
+        SqliteExporter {
+            db_path,
+            batch_size: DEFAULT_BATCH_SIZE,
+            if_exists: IfExists::default(),
+        }
+    }
+}
+
+/// Open output of a [`SqliteExporter`]. Entries are committed every
+/// `batch_size` rows. A new database is built under a temporary name and
+/// only moved into place by `finish`; an existing one being added to keeps
+/// the batches committed before an abort.
+pub struct SqliteWriter<'a> {
+    exporter: &'a SqliteExporter,
+    conn: Connection,
+    /// Temporary file of a new database.
+    new_db: Option<PendingPath>,
+    pending: usize,
+    files: usize,
+}
+
+impl StreamingExporter for SqliteExporter {
+    type Writer<'a> = SqliteWriter<'a>;
+
+    async fn begin(&self) -> Result<SqliteWriter<'_>, BridgeError> {
+        let new_db = if self.if_exists == IfExists::Overwrite && self.db_path.exists() {
+            None
+        } else {
+            Some(PendingPath::new(&self.db_path, self.if_exists)?)
+        };
+        let path = new_db.as_ref().map_or(self.db_path.as_path(), PendingPath::tmp);
+        let conn = Connection::open(path)
+            .map_err(|e| BridgeError::Database(format!("SQLite open failed: {}", e)))?;
+
+        conn.pragma_update(None, "foreign_keys", "ON")?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        prepare_schema(&conn)?;
+        conn.execute_batch("BEGIN")?;
+
+        Ok(SqliteWriter { exporter: self, conn, new_db, pending: 0, files: 0 })
+    }
+}
+
+impl AssignmentWriter for SqliteWriter<'_> {
+    async fn write_assignment(&mut self, file: &BridgeParsedAssignment) -> Result<(), BridgeError> {
+        let batch_size = self.exporter.batch_size.max(1);
+        insert_file(&self.conn, file)?;
+        for entry in &file.lines {
+            insert_line(&self.conn, &file.file_sha, entry, file.published)?;
+            self.pending += 1;
+            if self.pending == batch_size {
+                self.conn.execute_batch("COMMIT; BEGIN")?;
+                self.pending = 0;
+            }
+        }
+        self.files += 1;
+        Ok(())
+    }
+
+    async fn finish(self) -> Result<(), BridgeError> {
+        self.conn.execute_batch("COMMIT")?;
+        leave_wal(&self.conn)?;
+        self.conn.close().map_err(|(_, e)| e)?;
+        let path = match self.new_db {
+            Some(new_db) => new_db.commit()?,
+            None => self.exporter.db_path.clone(),
+        };
+        info!(" Exported {} files to {}", self.files, path.display());
+        Ok(())
+    }
+
+    async fn abort(self) -> Result<(), BridgeError> {
+        self.conn.execute_batch("ROLLBACK")?;
+        leave_wal(&self.conn)
+    }
+}
+
+/// Checkpoint the write-ahead log and go back to a rollback journal. WAL mode
+/// is persistent, and would keep `-wal`/`-shm` files next to the database.
+fn leave_wal(conn: &Connection) -> Result<(), BridgeError> {
+    conn.pragma_update(None, "journal_mode", "DELETE")
+        .map_err(|e| BridgeError::Database(format!("Leaving SQLite WAL mode failed: {}", e)))
+}
+
+/// Ensure both tables and their indexes exist.
+fn prepare_schema(conn: &Connection) -> Result<(), BridgeError> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS bridge_file (
+            sha TEXT PRIMARY KEY,
+            header TEXT NOT NULL,
+            published TIMESTAMP NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS bridge_entry (
+            sha TEXT PRIMARY KEY,
+            fingerprint TEXT NOT NULL,
+            method TEXT NOT NULL,
+            file_sha TEXT REFERENCES bridge_file(sha),
+            transport TEXT,
+            ip TEXT,
+            block TEXT,
+            distributed BOOLEAN,
+            state TEXT,
+            bandwidth TEXT,
+            ratio REAL,
+            published TIMESTAMP NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS bridge_entry_fingerprint_idx ON bridge_entry (fingerprint);
+        CREATE INDEX IF NOT EXISTS bridge_entry_published_idx ON bridge_entry (published);
+        CREATE INDEX IF NOT EXISTS bridge_file_published_idx ON bridge_file (published);",
+    )
+    .map_err(|e| BridgeError::Database(format!("Creating SQLite schema failed: {}", e)))
+}
+
+/// Insert one bridge_file row
+fn insert_file(tx: &Connection, file: &BridgeParsedAssignment) -> Result<(), BridgeError> {
+    tx.prepare_cached(
+        "INSERT INTO bridge_file (sha, header, published)
+         VALUES (?1, ?2, ?3) ON CONFLICT DO NOTHING",
+    )?
+    .execute(params![file.file_sha, file.header, to_sqlite_timestamp(file.published)?])
+    .map_err(|e| BridgeError::Database(format!("Insert into bridge_file failed: {}", e)))?;
+    Ok(())
+}
+
+/// Insert one bridge_entry row
+fn insert_line(tx: &Connection, file_sha: &str, entry: &BridgeLineEntry, millis: i64) -> Result<(), BridgeError> {
+    tx.prepare_cached(
+        "INSERT INTO bridge_entry (
+            sha, fingerprint, method, file_sha,
+            transport, ip, block, distributed,
+            state, bandwidth, ratio, published
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+        ON CONFLICT DO NOTHING",
+    )?
+    .execute(params![
+        entry.sha,
+        entry.fingerprint,
+        entry.distribution_method,
+        file_sha,
+        entry.transport,
+        entry.ip,
+        entry.blocklist,
+        entry.distributed,
+        entry.state,
+        entry.bandwidth,
+        entry.ratio,
+        to_sqlite_timestamp(millis)?,
+    ])
+    .map_err(|e| BridgeError::Database(format!("Insert into bridge_entry failed: {}", e)))?;
+    Ok(())
+}
+
+/// Render millis as `YYYY-MM-DD HH:MM:SS.SSS`, which SQLite's date functions understand.
+fn to_sqlite_timestamp(ms: i64) -> Result<String, BridgeError> {
+    millis_to_utc(ms)
+        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S%.3f").to_string())
+        .ok_or_else(|| BridgeError::Export("Invalid timestamp conversion".into()))
+}
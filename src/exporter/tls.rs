@@ -0,0 +1,336 @@
+use std::fs::File;
+use std::future::Future;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, CertificateError, ClientConfig, OwnedTrustAnchor, PrivateKey, RootCertStore, ServerName};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_postgres::tls::{ChannelBinding, MakeTlsConnect, TlsConnect, TlsStream};
+use crate::error::BridgeError;
+
+/// libpq-style `sslmode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SslMode {
+    /// Plain TCP only.
+    Disable,
+    /// Use TLS if the server offers it, without verifying the certificate.
+    #[default]
+    Prefer,
+    /// Always use TLS. The certificate is only verified if a root CA is given.
+    Require,
+    /// Always use TLS and check that the certificate chains to a trusted root.
+    VerifyCa,
+    /// Like `VerifyCa`, and the certificate must also match the host name.
+    VerifyFull,
+}
+
+impl SslMode {
+    /// Closest mode tokio-postgres understands; verification is done by our connector.
+    pub(crate) fn to_postgres(self) -> tokio_postgres::config::SslMode {
+        match self {
+            SslMode::Disable => tokio_postgres::config::SslMode::Disable,
+            SslMode::Prefer => tokio_postgres::config::SslMode::Prefer,
+            SslMode::Require | SslMode::VerifyCa | SslMode::VerifyFull => tokio_postgres::config::SslMode::Require,
+        }
+    }
+
+    /// Map the `sslmode` parsed from a connection string.
+    pub(crate) fn from_postgres(mode: tokio_postgres::config::SslMode) -> Self {
+        match mode {
+            tokio_postgres::config::SslMode::Disable => SslMode::Disable,
+            tokio_postgres::config::SslMode::Require => SslMode::Require,
+            _ => SslMode::Prefer,
+        }
+    }
+}
+
+impl FromStr for SslMode {
+    type Err = BridgeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "disable" => Ok(SslMode::Disable),
+            "prefer" => Ok(SslMode::Prefer),
+            "require" => Ok(SslMode::Require),
+            "verify-ca" | "verify_ca" => Ok(SslMode::VerifyCa),
+            "verify-full" | "verify_full" => Ok(SslMode::VerifyFull),
+            other => Err(BridgeError::Config(format!(
+                "Unknown sslmode '{}', expected disable, prefer, require, verify-ca or verify-full",
+                other
+            ))),
+        }
+    }
+}
+
+/// `value` if it is one of the verifying modes tokio-postgres cannot parse.
+fn verify_mode(value: &str) -> Option<SslMode> {
+    value.parse().ok().filter(|mode| matches!(mode, SslMode::VerifyCa | SslMode::VerifyFull))
+}
+
+/// Remove `sslmode=verify-ca` or `sslmode=verify-full` from a key/value or
+/// URL connection string, as tokio-postgres rejects them; returns the rest
+/// of the connection string and the mode that was removed.
+pub(crate) fn take_verify_mode(conn_str: &str) -> (String, Option<SslMode>) {
+    if conn_str.contains("://") {
+        if let Some((base, query)) = conn_str.split_once('?') {
+            let mut mode = None;
+            let params: Vec<&str> = query
+                .split('&')
+                .filter(|param| match param.split_once('=') {
+                    Some(("sslmode", value)) => match verify_mode(value) {
+                        Some(verify) => {
+                            mode = Some(verify);
+                            false
+                        }
+                        None => true,
+                    },
+                    _ => true,
+                })
+                .collect();
+            let rest = if params.is_empty() { base.to_string() } else { format!("{}?{}", base, params.join("&")) };
+            return (rest, mode);
+        }
+        return (conn_str.to_string(), None);
+    }
+
+    // key = value pairs; values may be quoted, and spaces around `=` are allowed
+    for (start, _) in conn_str.match_indices("sslmode") {
+        if !conn_str[..start].chars().next_back().is_none_or(char::is_whitespace) {
+            continue;
+        }
+        let after_key = conn_str[start + "sslmode".len()..].trim_start();
+        let Some(after_eq) = after_key.strip_prefix('=') else { continue };
+        let after_eq = after_eq.trim_start();
+        let (value, after_value) = match after_eq.strip_prefix('\'') {
+            Some(quoted) => match quoted.split_once('\'') {
+                Some((value, rest)) => (value, rest),
+                None => continue,
+            },
+            None => {
+                let end = after_eq.find(char::is_whitespace).unwrap_or(after_eq.len());
+                after_eq.split_at(end)
+            }
+        };
+        if let Some(mode) = verify_mode(value) {
+            let end = conn_str.len() - after_value.len();
+            let rest = format!("{}{}", &conn_str[..start], &conn_str[end..]);
+            return (rest.trim().to_string(), Some(mode));
+        }
+    }
+    (conn_str.to_string(), None)
+}
+
+/// TLS settings for PostgreSQL connections.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsConfig {
+    /// Overrides the `sslmode` of the connection string when set.
+    pub mode: Option<SslMode>,
+    /// PEM file with trusted root certificates; the webpki roots (as used by
+    /// the HTTP client) are trusted otherwise.
+    pub root_cert: Option<PathBuf>,
+    /// PEM client certificate chain, for servers that require certificate auth.
+    pub client_cert: Option<PathBuf>,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1) matching `client_cert`.
+    pub client_key: Option<PathBuf>,
+}
+
+impl TlsConfig {
+    /// Build the rustls connector for `mode`, or `None` when TLS is disabled.
+    pub(crate) fn connector(&self, mode: SslMode) -> Result<Option<MakeRustlsConnect>, BridgeError> {
+        if mode == SslMode::Disable {
+            return Ok(None);
+        }
+
+        let roots = self.root_store()?;
+        let verify = match mode {
+            SslMode::Require if self.root_cert.is_some() => Verify::Chain,
+            SslMode::Disable | SslMode::Prefer | SslMode::Require => Verify::Nothing,
+            SslMode::VerifyCa => Verify::Chain,
+            SslMode::VerifyFull => Verify::Full,
+        };
+        let verifier = Arc::new(Verifier {
+            verify,
+            webpki: WebPkiVerifier::new(roots, None),
+        });
+
+        let builder = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(verifier);
+        let config = match (&self.client_cert, &self.client_key) {
+            (Some(cert), Some(key)) => builder
+                .with_client_auth_cert(load_certs(cert)?, load_key(key)?)
+                .map_err(|e| BridgeError::Config(format!("Invalid client certificate or key: {}", e)))?,
+            (None, None) => builder.with_no_client_auth(),
+            _ => {
+                return Err(BridgeError::Config(
+                    "A client certificate and key must be given together".into(),
+                ))
+            }
+        };
+
+        Ok(Some(MakeRustlsConnect {
+            config: Arc::new(config),
+        }))
+    }
+
+    fn root_store(&self) -> Result<RootCertStore, BridgeError> {
+        let mut roots = RootCertStore::empty();
+        match self.root_cert {
+            Some(ref path) => {
+                for cert in load_certs(path)? {
+                    roots
+                        .add(&cert)
+                        .map_err(|e| BridgeError::Config(format!("Invalid root certificate in {}: {}", path.display(), e)))?;
+                }
+            }
+            None => roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+                OwnedTrustAnchor::from_subject_spki_name_constraints(ta.subject, ta.spki, ta.name_constraints)
+            })),
+        }
+        Ok(roots)
+    }
+}
+
+fn load_certs(path: &Path) -> Result<Vec<Certificate>, BridgeError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let certs = rustls_pemfile::certs(&mut reader)?;
+    if certs.is_empty() {
+        return Err(BridgeError::Config(format!("No certificates found in {}", path.display())));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn load_key(path: &Path) -> Result<PrivateKey, BridgeError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    while let Some(item) = rustls_pemfile::read_one(&mut reader)? {
+        match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => return Ok(PrivateKey(key)),
+            _ => {}
+        }
+    }
+    Err(BridgeError::Config(format!("No private key found in {}", path.display())))
+}
+
+/// How much of the server certificate is checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verify {
+    Nothing,
+    Chain,
+    Full,
+}
+
+struct Verifier {
+    verify: Verify,
+    webpki: WebPkiVerifier,
+}
+
+impl ServerCertVerifier for Verifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if self.verify == Verify::Nothing {
+            return Ok(ServerCertVerified::assertion());
+        }
+        match self
+            .webpki
+            .verify_server_cert(end_entity, intermediates, server_name, scts, ocsp_response, now)
+        {
+            // verify-ca trusts any host name on a certificate from a trusted CA
+            Err(rustls::Error::InvalidCertificate(CertificateError::NotValidForName)) if self.verify == Verify::Chain => {
+                Ok(ServerCertVerified::assertion())
+            }
+            result => result,
+        }
+    }
+}
+
+/// `MakeTlsConnect` for tokio-postgres backed by rustls.
+#[derive(Clone)]
+pub(crate) struct MakeRustlsConnect {
+    config: Arc<ClientConfig>,
+}
+
+impl<S> MakeTlsConnect<S> for MakeRustlsConnect
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Stream = RustlsStream<S>;
+    type TlsConnect = RustlsConnect;
+    type Error = io::Error;
+
+    fn make_tls_connect(&mut self, domain: &str) -> Result<RustlsConnect, io::Error> {
+        Ok(RustlsConnect {
+            config: self.config.clone(),
+            domain: domain.to_string(),
+        })
+    }
+}
+
+pub(crate) struct RustlsConnect {
+    config: Arc<ClientConfig>,
+    /// Validated in `connect`, so hosts that never negotiate TLS need no valid name.
+    domain: String,
+}
+
+impl<S> TlsConnect<S> for RustlsConnect
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Stream = RustlsStream<S>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<RustlsStream<S>>> + Send>>;
+
+    fn connect(self, stream: S) -> Self::Future {
+        Box::pin(async move {
+            let server_name = ServerName::try_from(self.domain.as_str()).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid server name '{}'", self.domain))
+            })?;
+            tokio_rustls::TlsConnector::from(self.config)
+                .connect(server_name, stream)
+                .await
+                .map(RustlsStream)
+        })
+    }
+}
+
+pub(crate) struct RustlsStream<S>(tokio_rustls::client::TlsStream<S>);
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for RustlsStream<S> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for RustlsStream<S> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> TlsStream for RustlsStream<S> {
+    /// Channel binding is not offered, so SCRAM falls back to the non-PLUS variant.
+    fn channel_binding(&self) -> ChannelBinding {
+        ChannelBinding::none()
+    }
+}
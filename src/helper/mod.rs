@@ -1,3 +1,7 @@
+pub mod country;
 pub mod digest;
+pub mod time;
 
+pub use country::{is_country_code, split_country_codes};
 pub use digest::{Digest, Sha256Digest};
+pub use time::millis_to_utc;
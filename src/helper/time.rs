@@ -0,0 +1,6 @@
+use chrono::{DateTime, Utc};
+
+/// Convert i64 timestamp in millis to a UTC DateTime, or `None` if out of range.
+pub fn millis_to_utc(ms: i64) -> Option<DateTime<Utc>> {
+    DateTime::<Utc>::from_timestamp(ms.div_euclid(1000), (ms.rem_euclid(1000) * 1_000_000) as u32)
+}
@@ -23,6 +23,7 @@ pub mod transformer;
 pub mod exporter;
 pub mod helper;
 pub mod error;
+pub mod analysis;
 
 // Re-export commonly used functions
 pub use collector::local::read_local_files;
@@ -6,21 +6,52 @@ use bridge_parser::{
 };
 use bridge_parser::exporter::{
     Exporter, 
+    TableExporter,
     PostgresExporter, 
+    ExportMode,
+    Layout,
+    LoadMode,
+    OlderThan,
+    PruneAction,
+    SummaryView,
+    SslMode,
+    TlsConfig,
     CsvExporter,
+    JsonExporter,
+    NdjsonExporter,
+    PrometheusExporter,
+    InfluxExporter,
+    IfExists,
+    Compression,
+    FailurePolicy,
+    FanOutExporter,
+    OutputSpec,
+    ColumnSchema,
+    QuoteStyle,
+    TimestampFormat,
 };
+use bridge_parser::analysis::{
+    analyze_blocklist, analyze_churn, analyze_flows, check_balance, detect_anomalies,
+    BalanceConfig, FlowReport, MetricTable, RuleSet, Window,
+};
+use chrono::NaiveDate;
 #[cfg(feature = "parquet_export")]
-use bridge_parser::exporter::ParquetExporter;
+use bridge_parser::exporter::{ParquetCompression, ParquetExporter};
+#[cfg(feature = "arrow_export")]
+use bridge_parser::exporter::{ArrowIpcExporter, IpcFormat};
+#[cfg(feature = "sqlite_export")]
+use bridge_parser::exporter::SqliteExporter;
 use bridge_parser::collector::BridgeRawFile;
+use bridge_parser::helper::{Digest, Sha256Digest};
 use bridge_parser::error::BridgeError;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use tracing::{info, error};
 use std::error::Error;
 use std::path::{Path, PathBuf};
 use dotenvy::dotenv;
 
 /// Command-line options for bridge-parser
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 struct Options {
     /// URL base for the Tor CollecTor index
@@ -43,10 +74,23 @@ struct Options {
     #[arg(long)]
     local_dir: Option<String>,
 
-    ///Output format: postgres (default), csv, or parquet
+    ///Output format: postgres (default), csv, parquet, arrow, json, ndjson, sqlite, prometheus or influx
     #[arg(long, default_value = "postgres")]
     format: String,
 
+    ///Additional outputs, repeatable, each FORMAT or FORMAT=TARGET (a path, or a connection
+    ///string for postgres), e.g. --output postgres --output parquet=entries.parquet.
+    ///;KEY=VALUE options override the matching flags for that output only
+    ///(e.g. csv=stats.csv;columns=fingerprint;compression=gzip).
+    ///Replaces --format for the export; the data is parsed once and written to each in turn
+    #[arg(long, value_name = "FORMAT[=TARGET][;KEY=VALUE...]")]
+    output: Vec<OutputSpec>,
+
+    ///What a failing --output means for the outputs after it: continue (write them anyway)
+    ///or abort (skip them). The run fails if any output failed either way
+    #[arg(long, default_value = "continue")]
+    on_output_failure: FailurePolicy,
+
     ///Dry run: only parse, do not export to DB or file
     #[arg(long, default_value_t = false)]
     dry_run: bool,
@@ -62,18 +106,569 @@ struct Options {
     ///Parquet export file path (used if --format=parquet)
     #[arg(long, default_value = "output.parquet")]
     parquet_output: String,
+
+    ///Parquet compression: none, snappy, gzip[:level] or zstd[:level] (used if --format=parquet)
+    #[cfg(feature = "parquet_export")]
+    #[arg(long, default_value = "snappy")]
+    parquet_compression: ParquetCompression,
+
+    ///Entries per Parquet row group; each group is written as soon as it is full
+    #[cfg(feature = "parquet_export")]
+    #[arg(long, default_value_t = 100_000)]
+    parquet_row_group_size: usize,
+
+    ///Treat --parquet-output as a dataset directory partitioned as year=YYYY/month=MM/,
+    ///appending only files not yet recorded in its _manifest.json
+    #[cfg(feature = "parquet_export")]
+    #[arg(long, default_value_t = false)]
+    parquet_dataset: bool,
+
+    ///Arrow IPC export file path, or '-' for stdout (used if --format=arrow)
+    #[arg(long, default_value = "output.arrow")]
+    arrow_output: PathBuf,
+
+    ///Arrow IPC format: file (random access) or stream (for piping) (used if --format=arrow)
+    #[cfg(feature = "arrow_export")]
+    #[arg(long, default_value = "file")]
+    arrow_format: IpcFormat,
+
+    ///JSON export file path, or '-' for stdout (used if --format=json)
+    #[arg(long, default_value = "output.json")]
+    json_output: PathBuf,
+
+    ///NDJSON export file path, or '-' for stdout (used if --format=ndjson)
+    #[arg(long, default_value = "output.ndjson")]
+    ndjson_output: PathBuf,
+
+    ///SQLite database path (used if --format=sqlite)
+    #[arg(long, default_value = "output.sqlite")]
+    sqlite_output: PathBuf,
+
+    ///Prometheus textfile with the latest assignment's pool composition (used if --format=prometheus)
+    #[arg(long, default_value = "bridge_pool.prom")]
+    prometheus_output: PathBuf,
+
+    ///InfluxDB line-protocol file, or '-' for stdout (used if --format=influx)
+    #[arg(long)]
+    influx_output: Option<PathBuf>,
+
+    ///InfluxDB write endpoint to POST the lines to, --batch-size lines per request
+    ///(e.g. http://localhost:8086/api/v2/write?org=tor&bucket=bridges)
+    #[arg(long)]
+    influx_url: Option<String>,
+
+    ///InfluxDB API token, sent as 'Authorization: Token ...'
+    #[arg(long, env = "INFLUX_TOKEN", hide_env_values = true)]
+    influx_token: Option<String>,
+
+    ///What file outputs do when the file already exists: overwrite, refuse (fail the output)
+    ///or version (write name.1.ext, name.2.ext, ...). SQLite overwrite adds to the database
+    #[arg(long, default_value = "overwrite")]
+    if_exists: IfExists,
+
+    ///Compression of CSV, JSON and NDJSON output: auto (by the .gz, .xz or .zst extension),
+    ///none, gzip, xz or zstd
+    #[arg(long, default_value = "auto")]
+    compression: Compression,
+
+    ///Compression level (gzip and xz: 0-9, default 6; zstd: 1-22, default 3)
+    #[arg(long, allow_negative_numbers = true)]
+    compression_level: Option<i32>,
+
+    ///How PostgreSQL entries are loaded: copy (bulk, default) or insert (row by row)
+    #[arg(long, default_value = "copy")]
+    pg_load: LoadMode,
+
+    ///How already loaded PostgreSQL data is treated: append (insert new rows only),
+    ///skip-existing (skip loaded files before parsing), upsert (update changed rows)
+    ///or replace-file (delete and reinsert a loaded file's entries)
+    #[arg(long, default_value = "append")]
+    pg_mode: ExportMode,
+
+    ///PostgreSQL table layout: flat (bridge_file/bridge_entry) or normalized (bridge_norm schema)
+    #[arg(long, default_value = "flat")]
+    pg_layout: Layout,
+
+    ///Partition bridge_entry by month on `published`; an existing table is converted once
+    ///and missing monthly partitions are created on every export (flat layout only)
+    #[arg(long, default_value_t = false)]
+    pg_partition: bool,
+
+    ///Comma-separated materialised views to create and refresh concurrently after each
+    ///PostgreSQL load: daily (counts per day/distributor/transport/state), pool (composition
+    ///of the latest file) and seen (first/last seen per fingerprint)
+    #[arg(long, value_delimiter = ',')]
+    pg_views: Vec<SummaryView>,
+
+    ///PostgreSQL TLS mode: disable, prefer, require, verify-ca or verify-full
+    ///(default: the connection string's sslmode, or prefer)
+    #[arg(long)]
+    pg_sslmode: Option<SslMode>,
+
+    ///PEM file with the root CA(s) trusted for the PostgreSQL server certificate
+    #[arg(long)]
+    pg_sslrootcert: Option<PathBuf>,
+
+    ///PEM client certificate for PostgreSQL certificate authentication
+    #[arg(long, requires = "pg_sslkey")]
+    pg_sslcert: Option<PathBuf>,
+
+    ///PEM private key for --pg-sslcert
+    #[arg(long, requires = "pg_sslcert")]
+    pg_sslkey: Option<PathBuf>,
+
+    ///Number of entries written per database transaction/batch
+    #[arg(long, default_value_t = 10_000)]
+    batch_size: usize,
+
+    ///Pretty-print JSON output (--format=json only)
+    #[arg(long, default_value_t = false)]
+    pretty: bool,
+
+    ///Comma-separated, ordered list of columns for CSV, Parquet and Arrow output
+    ///(default: every column except `header`)
+    #[arg(long)]
+    columns: Option<String>,
+
+    ///Rendering of the publication time: iso8601, epoch-ms or epoch-s
+    #[arg(long, default_value = "epoch-ms")]
+    timestamp_format: TimestampFormat,
+
+    ///CSV field delimiter (a single ASCII character, e.g. ';' or '\t')
+    #[arg(long, default_value = ",")]
+    csv_delimiter: String,
+
+    ///CSV quoting: necessary, always, non-numeric or never
+    #[arg(long, default_value = "necessary")]
+    csv_quote: QuoteStyle,
+
+    ///Run an analysis instead of exporting raw assignments: churn, anomalies, blocklist, balance or flow
+    #[arg(long)]
+    analysis: Option<String>,
+
+    ///Aggregation window for time-series analyses: daily, weekly or monthly
+    #[arg(long, default_value = "daily")]
+    window: Window,
+
+    ///Directory that analysis tables are written into (one file per table)
+    #[arg(long, default_value = ".")]
+    analysis_dir: String,
+
+    ///JSON file with anomaly detection rules (built-in defaults if omitted)
+    #[arg(long)]
+    rules: Option<PathBuf>,
+
+    ///JSON file with the expected distributor proportions (required for --analysis=balance)
+    #[arg(long)]
+    balance_config: Option<PathBuf>,
+
+    ///Start date (YYYY-MM-DD) for --analysis=flow; compares two dates instead of consecutive snapshots
+    #[arg(long, requires = "to")]
+    from: Option<NaiveDate>,
+
+    ///End date (YYYY-MM-DD) for --analysis=flow
+    #[arg(long, requires = "from")]
+    to: Option<NaiveDate>,
+
+    ///Exit with a non-zero status if any anomaly alert fires or distributor is flagged
+    #[arg(long, default_value_t = false)]
+    fail_on_alert: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Maintenance commands that run instead of the fetch/parse/export pipeline
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Manage the PostgreSQL schema (uses --db)
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    /// Detach, drop or archive old monthly partitions of bridge_entry (uses --db)
+    Prune {
+        ///Prune partitions that end before this date (YYYY-MM-DD) or age (e.g. 90d, 12w, 6m, 2y)
+        #[arg(long)]
+        older_than: OlderThan,
+
+        ///Drop the partitions instead of detaching them into standalone tables
+        #[arg(long, default_value_t = false, conflicts_with = "archive")]
+        drop: bool,
+
+        ///Write each partition to DIR/<partition>.parquet, then drop it
+        #[arg(long, value_name = "DIR")]
+        archive: Option<PathBuf>,
+
+        ///Only list the partitions that would be pruned
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum MigrateAction {
+    /// Apply all pending migrations
+    Up {
+        ///Only list the migrations that would be applied
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+    /// Show applied and pending migrations
+    Status,
+}
+
+/// Build the PostgreSQL exporter from `--db` and the `--pg-*` options.
+fn postgres_exporter(opts: &Options) -> PostgresExporter {
+    PostgresExporter {
+        conn_str: opts.db.clone(),
+        truncate: opts.clear,
+        load_mode: opts.pg_load,
+        batch_size: opts.batch_size,
+        tls: TlsConfig {
+            mode: opts.pg_sslmode,
+            root_cert: opts.pg_sslrootcert.clone(),
+            client_cert: opts.pg_sslcert.clone(),
+            client_key: opts.pg_sslkey.clone(),
+        },
+        layout: opts.pg_layout,
+        mode: opts.pg_mode,
+        partition: opts.pg_partition,
+        views: opts.pg_views.clone(),
+    }
+}
+
+/// Run a `migrate` action against `--db`.
+fn run_migrate(action: &MigrateAction, opts: &Options) -> Result<(), BridgeError> {
+    let exporter = postgres_exporter(opts);
+    match action {
+        MigrateAction::Up { dry_run } => {
+            let migrations = exporter.migrate(*dry_run)?;
+            let verb = if *dry_run { "Would apply" } else { "Applied" };
+            for m in &migrations {
+                println!("{} {:04} {}", verb, m.version, m.name);
+                if *dry_run {
+                    println!("{}", m.sql.trim_end());
+                }
+            }
+            if migrations.is_empty() {
+                println!("Schema is up to date");
+            }
+        }
+        MigrateAction::Status => {
+            for s in exporter.migration_status()? {
+                let applied = s
+                    .applied_at
+                    .map(|at| format!("applied {}", at.to_rfc3339()))
+                    .unwrap_or_else(|| "pending".to_string());
+                println!("{:04} {:<24} {}", s.version, s.name, applied);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Run `prune` against `--db`.
+fn run_prune(older_than: OlderThan, action: PruneAction, dry_run: bool, opts: &Options) -> Result<(), BridgeError> {
+    let cutoff = older_than.cutoff(chrono::Utc::now().date_naive());
+    let pruned = postgres_exporter(opts).prune(cutoff, &action, dry_run)?;
+    let verb = match (dry_run, &action) {
+        (true, _) => "Would prune",
+        (false, PruneAction::Detach) => "Detached",
+        (false, PruneAction::Drop) => "Dropped",
+        (false, PruneAction::Archive(_)) => "Archived and dropped",
+    };
+    for p in &pruned {
+        println!("{} {} ({} to {}, {} rows)", verb, p.name, p.from, p.to, p.rows);
+    }
+    if pruned.is_empty() {
+        println!("No partitions end before {}", cutoff);
+    }
+    Ok(())
+}
+
+/// Build the column schema from `--columns` and `--timestamp-format`.
+fn column_schema(opts: &Options) -> Result<ColumnSchema, BridgeError> {
+    match opts.columns {
+        Some(ref list) => ColumnSchema::parse(list, opts.timestamp_format),
+        None => Ok(ColumnSchema {
+            timestamp_format: opts.timestamp_format,
+            ..ColumnSchema::default()
+        }),
+    }
+}
+
+/// Where the assignments come from, as recorded in Parquet and Arrow metadata.
+#[cfg(any(feature = "parquet_export", feature = "arrow_export"))]
+fn source(opts: &Options) -> String {
+    match opts.local_dir {
+        Some(ref dir) => dir.clone(),
+        None => format!("{}/{}", opts.base.trim_end_matches('/'), opts.path.trim_start_matches('/')),
+    }
+}
+
+/// Build a CSV exporter for `path` from the CSV-related options.
+fn csv_exporter(path: PathBuf, opts: &Options) -> Result<CsvExporter, BridgeError> {
+    let delimiter = match opts.csv_delimiter.as_str() {
+        "\\t" | "tab" => b'\t',
+        d if d.len() == 1 && d.is_ascii() => d.as_bytes()[0],
+        other => {
+            return Err(BridgeError::Config(format!(
+                "CSV delimiter must be a single ASCII character, got '{}'",
+                other
+            )))
+        }
+    };
+
+    Ok(CsvExporter {
+        output_path: path,
+        schema: column_schema(opts)?,
+        delimiter,
+        quote_style: opts.csv_quote,
+        if_exists: opts.if_exists,
+        compression: opts.compression,
+        compression_level: opts.compression_level,
+    })
+}
+
+/// The outputs of a run: every `--output`, or just `--format` if none was given.
+fn outputs(opts: &Options) -> Result<Vec<OutputSpec>, BridgeError> {
+    if opts.output.is_empty() {
+        Ok(vec![opts.format.parse()?])
+    } else {
+        Ok(opts.output.clone())
+    }
+}
+
+/// Parse the value of a per-output option.
+fn option_value<T>(key: &str, value: &str) -> Result<T, BridgeError>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    value.parse().map_err(|e| {
+        BridgeError::Config(format!("Invalid value '{}' for output option '{}': {}", value, key, e))
+    })
+}
+
+/// `opts` with the options of `spec` in place of the matching flags. Keys are
+/// the flag names without `--`; `compression` of a parquet output sets
+/// `--parquet-compression`.
+fn output_options(spec: &OutputSpec, opts: &Options) -> Result<Options, BridgeError> {
+    let mut opts = opts.clone();
+    for (key, value) in &spec.options {
+        match key.replace('_', "-").as_str() {
+            "columns" => opts.columns = Some(value.clone()),
+            "timestamp-format" => opts.timestamp_format = value.parse()?,
+            #[cfg(feature = "parquet_export")]
+            "compression" if spec.format == "parquet" => opts.parquet_compression = value.parse()?,
+            "compression" => opts.compression = value.parse()?,
+            "compression-level" => opts.compression_level = Some(option_value(key, value)?),
+            "if-exists" => opts.if_exists = value.parse()?,
+            "batch-size" => opts.batch_size = option_value(key, value)?,
+            "pretty" => opts.pretty = option_value(key, value)?,
+            "csv-delimiter" => opts.csv_delimiter = value.clone(),
+            "csv-quote" => opts.csv_quote = value.parse()?,
+            #[cfg(feature = "parquet_export")]
+            "parquet-compression" => opts.parquet_compression = value.parse()?,
+            #[cfg(feature = "parquet_export")]
+            "parquet-row-group-size" => opts.parquet_row_group_size = option_value(key, value)?,
+            #[cfg(feature = "parquet_export")]
+            "parquet-dataset" => opts.parquet_dataset = option_value(key, value)?,
+            #[cfg(feature = "arrow_export")]
+            "arrow-format" => opts.arrow_format = value.parse()?,
+            "pg-load" => opts.pg_load = value.parse()?,
+            "pg-mode" => opts.pg_mode = value.parse()?,
+            "pg-layout" => opts.pg_layout = value.parse()?,
+            "pg-partition" => opts.pg_partition = option_value(key, value)?,
+            "pg-views" => opts.pg_views = value.split(',').map(str::parse).collect::<Result<_, _>>()?,
+            _ => {
+                return Err(BridgeError::Config(format!(
+                    "Unknown option '{}' for output '{}'",
+                    key, spec.format
+                )))
+            }
+        }
+    }
+    Ok(opts)
+}
+
+/// PostgreSQL exporter of a `postgres` output, connecting to its target if it has one.
+fn postgres_output(spec: &OutputSpec, opts: &Options) -> PostgresExporter {
+    PostgresExporter {
+        conn_str: spec.target.clone().unwrap_or_else(|| opts.db.clone()),
+        ..postgres_exporter(opts)
+    }
+}
+
+/// Build the exporter of one output; its target, if any, replaces the
+/// format's own path option (or `--db` for postgres), and its options the
+/// matching flags.
+fn output_exporter(spec: &OutputSpec, opts: &Options) -> Result<Box<dyn Exporter>, BridgeError> {
+    let opts = &output_options(spec, opts)?;
+    #[cfg(not(all(feature = "sqlite_export", feature = "parquet_export", feature = "arrow_export")))]
+    let disabled = |feature: &str| {
+        BridgeError::Config(format!(
+            "{} export support not enabled. Compile with --features {}",
+            spec.format, feature
+        ))
+    };
+    match spec.format.as_str() {
+        "postgres" => Ok(Box::new(postgres_output(spec, opts))),
+        "csv" => Ok(Box::new(csv_exporter(spec.path_or(&opts.csv_output), opts)?)),
+        "json" => Ok(Box::new(JsonExporter {
+            output_path: spec.path_or(&opts.json_output),
+            pretty: opts.pretty,
+            if_exists: opts.if_exists,
+            compression: opts.compression,
+            compression_level: opts.compression_level,
+        })),
+        "ndjson" => Ok(Box::new(NdjsonExporter {
+            output_path: spec.path_or(&opts.ndjson_output),
+            if_exists: opts.if_exists,
+            compression: opts.compression,
+            compression_level: opts.compression_level,
+        })),
+        "prometheus" => Ok(Box::new(PrometheusExporter::new(spec.path_or(&opts.prometheus_output)))),
+        "influx" => Ok(Box::new(InfluxExporter {
+            write_url: opts.influx_url.clone(),
+            token: opts.influx_token.clone(),
+            batch_size: opts.batch_size,
+            if_exists: opts.if_exists,
+            compression: opts.compression,
+            compression_level: opts.compression_level,
+            ..InfluxExporter::new(spec.target.as_ref().map(PathBuf::from).or_else(|| opts.influx_output.clone()))
+        })),
+        #[cfg(feature = "sqlite_export")]
+        "sqlite" => Ok(Box::new(SqliteExporter {
+            db_path: spec.path_or(&opts.sqlite_output),
+            batch_size: opts.batch_size,
+            if_exists: opts.if_exists,
+        })),
+        #[cfg(not(feature = "sqlite_export"))]
+        "sqlite" => Err(disabled("sqlite_export")),
+        #[cfg(feature = "parquet_export")]
+        "parquet" => Ok(Box::new(ParquetExporter {
+            output_path: spec.path_or(&opts.parquet_output),
+            schema: column_schema(opts)?,
+            row_group_size: opts.parquet_row_group_size,
+            compression: opts.parquet_compression,
+            bloom_filter: true,
+            dataset: opts.parquet_dataset,
+            source: Some(source(opts)),
+            if_exists: opts.if_exists,
+        })),
+        #[cfg(not(feature = "parquet_export"))]
+        "parquet" => Err(disabled("parquet_export")),
+        #[cfg(feature = "arrow_export")]
+        "arrow" => Ok(Box::new(ArrowIpcExporter {
+            schema: column_schema(opts)?,
+            format: opts.arrow_format,
+            source: Some(source(opts)),
+            if_exists: opts.if_exists,
+            ..ArrowIpcExporter::new(spec.path_or(&opts.arrow_output))
+        })),
+        #[cfg(not(feature = "arrow_export"))]
+        "arrow" => Err(disabled("arrow_export")),
+        other => Err(BridgeError::Config(format!(
+            "Unsupported format: '{}'. Use postgres, csv, parquet, arrow, json, ndjson, sqlite, prometheus or influx",
+            other
+        ))),
+    }
+}
+
+/// Analyses accepted by `--analysis`.
+const ANALYSES: &[&str] = &["churn", "anomalies", "blocklist", "balance", "flow"];
+
+fn unsupported_analysis(analysis: &str) -> BridgeError {
+    BridgeError::Config(format!(
+        "Unsupported analysis: '{}'. Use --analysis=churn|anomalies|blocklist|balance|flow",
+        analysis
+    ))
+}
+
+/// Whether analysis tables can be written as `format`.
+fn check_analysis_format(format: &str) -> Result<(), BridgeError> {
+    match format {
+        "csv" => Ok(()),
+        "parquet" if cfg!(feature = "parquet_export") => Ok(()),
+        "parquet" => Err(BridgeError::Config(
+            "Parquet export support not enabled. Compile with --features parquet_export".into(),
+        )),
+        other => Err(BridgeError::Config(format!(
+            "Unsupported format for analysis output: '{}'. Use --format=csv|parquet",
+            other
+        ))),
+    }
+}
+
+/// Reject an unknown `--analysis` or a `--format` its tables cannot be
+/// written in, before any input is read.
+fn check_analysis(analysis: &str, opts: &Options) -> Result<(), BridgeError> {
+    if !ANALYSES.contains(&analysis) {
+        return Err(unsupported_analysis(analysis));
+    }
+    check_analysis_format(&opts.format)
+}
+
+/// Write analysis tables into `--analysis-dir` using the `--format` backend.
+fn export_tables(tables: &[MetricTable], opts: &Options) -> Result<(), BridgeError> {
+    check_analysis_format(&opts.format)?;
+    let dir = PathBuf::from(&opts.analysis_dir);
+    std::fs::create_dir_all(&dir)?;
+
+    for table in tables {
+        match opts.format.as_str() {
+            "csv" => {
+                let exporter = csv_exporter(dir.join(format!("{}.csv", table.name)), opts)?;
+                exporter.export_table(table)?;
+            }
+            #[cfg(feature = "parquet_export")]
+            "parquet" => {
+                let exporter = ParquetExporter {
+                    if_exists: opts.if_exists,
+                    ..ParquetExporter::new(dir.join(format!("{}.parquet", table.name)))
+                };
+                exporter.export_table(table)?;
+            }
+            other => return check_analysis_format(other),
+        }
+        info!(" Wrote analysis table '{}' ({} rows)", table.name, table.rows.len());
+    }
+
+    Ok(())
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    // Initialize tracing with env filter
+    // Initialize tracing with env filter; logs go to stderr so stdout can carry exported data
     tracing_subscriber::fmt()
         .with_env_filter(tracing_subscriber::EnvFilter::new("info"))
+        .with_writer(std::io::stderr)
         .try_init()
         .ok(); // Ignore if already initialized
         
     dotenv().ok();
     let opts = Options::parse();
 
+    if let Some(Command::Migrate { ref action }) = opts.command {
+        run_migrate(action, &opts)?;
+        return Ok(());
+    }
+
+    if let Some(Command::Prune { older_than, drop, ref archive, dry_run }) = opts.command {
+        let action = match archive {
+            Some(dir) => PruneAction::Archive(dir.clone()),
+            None if drop => PruneAction::Drop,
+            None => PruneAction::Detach,
+        };
+        run_prune(older_than, action, dry_run, &opts)?;
+        return Ok(());
+    }
+
+    if let Some(ref analysis) = opts.analysis {
+        check_analysis(analysis, &opts)?;
+    }
+
     info!("Starting bridge parser");
 
     //  Step 1: Read files either from local or fetch from Tor CollecTor
@@ -100,48 +695,88 @@ fn main() -> Result<(), Box<dyn Error>> {
         info!(" Truncated input to {} files due to --limit", max);
     }
 
+    //  Step 2b: With --pg-mode skip-existing, drop files already in the database before parsing
+    //  (only when PostgreSQL is the sole output, as the other outputs still need those files)
+    let sole_postgres = match outputs(&opts)?.as_slice() {
+        [spec] if spec.format == "postgres" => Some(postgres_output(spec, &output_options(spec, &opts)?)),
+        _ => None,
+    };
+    let skip_loaded = opts.analysis.is_none() && !opts.dry_run;
+    if let Some(exporter) = sole_postgres.filter(|e| skip_loaded && e.mode == ExportMode::SkipExisting) {
+        let hasher = Sha256Digest;
+        let shas: Vec<String> = content.iter().map(|f| hasher.hash_bytes(&f.raw)).collect();
+        let loaded = exporter.loaded_files(&shas)?;
+        let before = content.len();
+        let mut shas = shas.iter();
+        content.retain(|_| shas.next().is_some_and(|sha| !loaded.contains(sha)));
+        info!(" Skipping {} of {} files already loaded into PostgreSQL", before - content.len(), before);
+    }
+
     //  Step 3: Parse raw files and transform into bridge assignments
     let parsed = parse_files(content)?;
     let assignments = convert_to_assignments(parsed);
 
-    //  Step 4: Only export if dry-run is NOT set
-    if !opts.dry_run {
-        match opts.format.as_str() {
-            "postgres" => {
-                //  PostgreSQL backend
-                let exporter = PostgresExporter {
-                    conn_str: opts.db.clone(),
-                    truncate: opts.clear,
-                };
-                exporter.export(&assignments)?;
+    //  Step 3b: Run an analysis instead of a plain export if one was requested
+    if let Some(ref analysis) = opts.analysis {
+        let mut alerts = 0;
+        let mut flows: Option<FlowReport> = None;
+        let tables = match analysis.as_str() {
+            "churn" => analyze_churn(&assignments, opts.window)?.tables(),
+            "blocklist" => analyze_blocklist(&assignments, opts.window)?.tables(),
+            "flow" => {
+                let report = analyze_flows(&assignments, opts.from.zip(opts.to))?;
+                let tables = report.tables();
+                flows = Some(report);
+                tables
             }
-            "csv" => {
-                //  CSV backend: uses `--csv-output` path
-                let exporter = CsvExporter {
-                    output_path: PathBuf::from(opts.csv_output.clone()),
-                };
-                exporter.export(&assignments)?;
+            "balance" => {
+                let path = opts.balance_config.as_ref().ok_or_else(|| {
+                    BridgeError::Config("--balance-config is required for --analysis=balance".into())
+                })?;
+                let report = check_balance(&assignments, &BalanceConfig::from_file(path)?)?;
+                alerts = report.flagged();
+                report.tables()
             }
-            "parquet" => {
-                #[cfg(feature = "parquet_export")]
-                {
-                    let exporter = ParquetExporter {
-                        output_path: PathBuf::from(opts.parquet_output.clone()),
-                    };
-                    exporter.export(&assignments)?;
-                }
-                #[cfg(not(feature = "parquet_export"))]
-                {
-                    error!(" Parquet export support not enabled. Compile with --features parquet_export");
-                    std::process::exit(1);
-                }
+            "anomalies" => {
+                let rules = match opts.rules {
+                    Some(ref path) => RuleSet::from_file(path)?,
+                    None => RuleSet::default(),
+                };
+                let report = detect_anomalies(&assignments, &rules)?;
+                alerts = report.alerts.len();
+                report.tables()
             }
-            other => {
-                // Unknown backend
-                error!("Unsupported format: '{}'. Use --format=postgres|csv|parquet", other);
-                std::process::exit(1);
+            other => return Err(unsupported_analysis(other).into()),
+        };
+
+        if opts.dry_run {
+            info!(" Dry run mode enabled – skipping analysis export");
+        } else {
+            export_tables(&tables, &opts)?;
+            if let Some(report) = flows {
+                let path = PathBuf::from(&opts.analysis_dir).join("flows.json");
+                report.write_sankey_json(&path, opts.if_exists)?;
+                info!(" Wrote Sankey JSON to {}", path.display());
             }
         }
+
+        if opts.fail_on_alert && alerts > 0 {
+            error!(" {} alert(s) fired", alerts);
+            std::process::exit(2);
+        }
+
+        info!(" Done");
+        return Ok(());
+    }
+
+    //  Step 4: Only export if dry-run is NOT set
+    if !opts.dry_run {
+        //  Every output gets the same parsed assignments and reports on its own
+        let mut fanout = FanOutExporter { outputs: Vec::new(), policy: opts.on_output_failure };
+        for spec in outputs(&opts)? {
+            fanout.outputs.push((spec.to_string(), output_exporter(&spec, &opts)?));
+        }
+        fanout.export(&assignments)?;
     } else {
         // Dry-run: skip export, useful for debugging parsing
         info!(" Dry run mode enabled – skipping export step");
@@ -1,10 +1,12 @@
 use crate::collector::BridgeRawFile;
-use crate::helper::{Sha256Digest, Digest};
+use crate::helper::{Sha256Digest, Digest, split_country_codes};
 use crate::error::BridgeError;
 
 use chrono::NaiveDateTime;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use lazy_static::lazy_static;
+use tracing::warn;
 
 lazy_static! {
     static ref FINGERPRINT_REGEX: Regex = Regex::new(r"^[a-fA-F0-9]{40}$")
@@ -12,7 +14,7 @@ lazy_static! {
 }
 
 /// Represents a full parsed bridge assignment file (with header, SHA, and entries).
-#[derive(Debug, Clone)]  //  Add Clone here
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BridgeParsedAssignment {
     pub file_sha: String,
     pub published: i64,
@@ -21,7 +23,7 @@ pub struct BridgeParsedAssignment {
 }
 
 /// Represents an individual line in the bridge assignment (parsed into fields).
-#[derive(Debug, Clone)]  // Add Clone derive
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BridgeLineEntry {
     pub sha: String,
     pub fingerprint: String,
@@ -35,6 +37,33 @@ pub struct BridgeLineEntry {
     pub ratio: Option<f32>,
 }
 
+impl BridgeLineEntry {
+    /// Validated ISO 3166-1 alpha-2 codes from the `blocklist` field (lowercase).
+    pub fn blocked_countries(&self) -> Vec<String> {
+        self.blocklist
+            .as_deref()
+            .map(|list| split_country_codes(list).0)
+            .unwrap_or_default()
+    }
+
+    /// IP versions from the `ip` field, e.g. `4,6` -> `[4, 6]`; invalid values are skipped.
+    pub fn ip_versions(&self) -> Vec<u8> {
+        self.ip
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .filter(|v| !v.trim().is_empty())
+            .filter_map(|v| match v.trim().parse() {
+                Ok(version) => Some(version),
+                Err(_) => {
+                    warn!("Ignoring invalid IP version '{}' for {}", v, self.fingerprint);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
 /// Parse the list of bridge files into structured assignments.
 /// This performs fingerprint validation and computes SHA digests.
 pub fn parse_files(raw_files: Vec<BridgeRawFile>) -> Result<Vec<BridgeParsedAssignment>, BridgeError> {
@@ -126,7 +155,13 @@ pub fn parse_line(line: &str) -> Result<BridgeLineEntry, BridgeError> {
         match kv[0] {
             "transport" => entry.transport = Some(kv[1].to_string()),
             "ip" => entry.ip = Some(kv[1].to_string()),
-            "blocklist" => entry.blocklist = Some(kv[1].to_string()),
+            "blocklist" => {
+                let (_, invalid) = split_country_codes(kv[1]);
+                if !invalid.is_empty() {
+                    warn!("Ignoring invalid blocklist country codes {:?} for {}", invalid, entry.fingerprint);
+                }
+                entry.blocklist = Some(kv[1].to_string());
+            }
             "distributed" => entry.distributed = Some(kv[1] == "true"),
             "state" => entry.state = Some(kv[1].to_string()),
             "bandwidth" => entry.bandwidth = Some(kv[1].to_string()),
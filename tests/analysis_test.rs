@@ -0,0 +1,75 @@
+//! Tests for the churn and lifetime analysis
+
+use bridge_parser::analysis::{analyze_churn, Window};
+use chrono::NaiveDate;
+
+mod common;
+
+const A: &str = "00000000000000000000000000000000000000aa";
+const B: &str = "00000000000000000000000000000000000000bb";
+const C: &str = "00000000000000000000000000000000000000cc";
+
+fn line(fingerprint: &str, method: &str, transport: &str) -> String {
+    format!("{} {} transport={} ip=4 distributed=true state=functional", fingerprint, method, transport)
+}
+
+#[test]
+fn test_window_buckets() {
+    common::setup();
+    // 2022-04-09 is a Saturday
+    let millis = NaiveDate::from_ymd_opt(2022, 4, 9).unwrap()
+        .and_hms_opt(13, 0, 0).unwrap()
+        .and_utc()
+        .timestamp_millis();
+    assert_eq!(Window::Daily.bucket(millis).unwrap(), NaiveDate::from_ymd_opt(2022, 4, 9).unwrap());
+    assert_eq!(Window::Weekly.bucket(millis).unwrap(), NaiveDate::from_ymd_opt(2022, 4, 4).unwrap());
+    assert_eq!(Window::Monthly.bucket(millis).unwrap(), NaiveDate::from_ymd_opt(2022, 4, 1).unwrap());
+    assert!("fortnightly".parse::<Window>().is_err());
+}
+
+#[test]
+fn test_churn_counts_joins_and_leaves_per_distributor() {
+    common::setup();
+    let day2 = [line(A, "email", "obfs4"), line(C, "https", "obfs4")];
+    let day1 = [line(A, "email", "obfs4"), line(B, "https", "vanilla")];
+    // Deliberately out of order: the analysis sorts by publication time
+    let data = vec![
+        common::assignment("2022-04-10 00:00:00", &day2),
+        common::assignment("2022-04-09 00:00:00", &day1),
+    ];
+
+    let report = analyze_churn(&data, Window::Daily).unwrap();
+    assert_eq!(report.churn.len(), 1);
+    let https = &report.churn[0];
+    assert_eq!(https.window_start, NaiveDate::from_ymd_opt(2022, 4, 10).unwrap());
+    assert_eq!(https.distributor, "https");
+    assert_eq!((https.joined, https.left), (1, 1));
+}
+
+#[test]
+fn test_lifetime_and_survival_by_transport() {
+    common::setup();
+    let day1 = [line(A, "email", "obfs4"), line(B, "https", "obfs4")];
+    let day2 = [line(A, "email", "obfs4")];
+    let day3 = [line(A, "email", "obfs4")];
+    let data = vec![
+        common::assignment("2022-04-09 00:00:00", &day1),
+        common::assignment("2022-04-10 00:00:00", &day2),
+        common::assignment("2022-04-11 00:00:00", &day3),
+    ];
+
+    let report = analyze_churn(&data, Window::Daily).unwrap();
+    let obfs4 = report.lifetimes.iter().find(|l| l.transport == "obfs4").unwrap();
+    assert_eq!((obfs4.spans, obfs4.ended), (2, 1));
+    // B left after 24h, A is censored at 48h: survival drops to 0.5 at 24h
+    assert_eq!(obfs4.median_lifetime_hours, Some(24.0));
+
+    let curve: Vec<_> = report.survival.iter().filter(|p| p.transport == "obfs4").collect();
+    assert_eq!(curve.len(), 1);
+    assert_eq!((curve[0].at_risk, curve[0].events), (2, 1));
+    assert!((curve[0].survival - 0.5).abs() < f64::EPSILON);
+
+    let tables = report.tables();
+    let names: Vec<_> = tables.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, ["churn", "lifetime", "survival"]);
+}
@@ -0,0 +1,88 @@
+//! Tests for per-file statistics and anomaly detection rules
+
+use bridge_parser::analysis::anomaly::{Rule, RuleKind};
+use bridge_parser::analysis::{detect_anomalies, file_stats, RuleSet};
+
+mod common;
+
+fn entry(n: usize, method: &str, state: &str) -> String {
+    format!("{:040x} {} transport=obfs4 ip=4 distributed=true state={}", n, method, state)
+}
+
+/// One file per day with `email` and `https` bridges, `functional` of which are functional.
+fn day(date: &str, email: usize, https: usize, functional: usize) -> bridge_parser::transformer::BridgeParsedAssignment {
+    let lines: Vec<String> = (0..email + https)
+        .map(|n| {
+            let method = if n < email { "email" } else { "https" };
+            let state = if n < functional { "functional" } else { "dysfunctional" };
+            entry(n, method, state)
+        })
+        .collect();
+    common::assignment(&format!("{} 00:00:00", date), &lines)
+}
+
+#[test]
+fn test_file_stats_counts_and_shares() {
+    common::setup();
+    let stats = file_stats(&day("2022-04-09", 3, 1, 2));
+    assert_eq!(stats.get("total"), 4.0);
+    assert_eq!(stats.get("distributor.email"), 3.0);
+    assert_eq!(stats.get("distributor.moat"), 0.0);
+    assert_eq!(stats.get("functional_share"), 0.5);
+}
+
+#[test]
+fn test_default_rules_catch_empty_distributor_and_functional_drop() {
+    common::setup();
+    let data = vec![
+        day("2022-04-09", 4, 4, 8),
+        day("2022-04-10", 4, 4, 8),
+        day("2022-04-11", 8, 0, 2),
+    ];
+
+    let report = detect_anomalies(&data, &RuleSet::default()).unwrap();
+    let fired: Vec<_> = report.alerts.iter().map(|a| (a.rule.as_str(), a.metric.as_str())).collect();
+    assert_eq!(fired, [
+        ("empty-distributor", "distributor.https"),
+        ("functional-share-change", "functional_share"),
+    ]);
+    assert_eq!(report.alerts[1].baseline, Some(1.0));
+    assert_eq!(report.alerts[1].score, Some(-75.0));
+}
+
+#[test]
+fn test_z_score_rule_needs_history() {
+    common::setup();
+    let rules = RuleSet {
+        rules: vec![Rule {
+            name: "total-z".into(),
+            metric: "total".into(),
+            kind: RuleKind::ZScore { max_z: 3.0, baseline: 3 },
+        }],
+    };
+    let data = vec![
+        day("2022-04-09", 10, 0, 0),
+        day("2022-04-10", 11, 0, 0),
+        day("2022-04-11", 10, 0, 0),
+        day("2022-04-12", 30, 0, 0),
+    ];
+
+    let report = detect_anomalies(&data, &rules).unwrap();
+    assert_eq!(report.alerts.len(), 1);
+    assert!(report.alerts[0].score.unwrap() > 3.0);
+}
+
+#[test]
+fn test_rules_parse_from_json() {
+    common::setup();
+    let json = r#"{"rules": [
+        {"name": "min-total", "metric": "total", "type": "threshold", "min": 100},
+        {"name": "moat-change", "metric": "distributor.moat", "type": "percent_change", "max_change_pct": 20, "baseline": 7}
+    ]}"#;
+    let path = std::env::temp_dir().join("bridge_parser_rules_test.json");
+    std::fs::write(&path, json).unwrap();
+
+    let rules = RuleSet::from_file(&path).unwrap();
+    assert_eq!(rules.rules.len(), 2);
+    assert_eq!(rules.rules[0].kind, RuleKind::Threshold { min: Some(100.0), max: None });
+}
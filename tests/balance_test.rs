@@ -0,0 +1,61 @@
+//! Tests for the distributor balance check
+
+use std::collections::BTreeMap;
+use bridge_parser::analysis::balance::Provisioning;
+use bridge_parser::analysis::{check_balance, BalanceConfig};
+
+mod common;
+
+fn file(counts: &[(&str, usize)]) -> bridge_parser::transformer::BridgeParsedAssignment {
+    let mut lines = Vec::new();
+    for (method, n) in counts {
+        for _ in 0..*n {
+            lines.push(format!("{:040x} {} transport=obfs4", lines.len(), method));
+        }
+    }
+    common::assignment("2022-04-09 00:00:00", &lines)
+}
+
+fn half_and_half() -> BalanceConfig {
+    BalanceConfig {
+        proportions: BTreeMap::from([("email".to_string(), 50.0), ("https".to_string(), 50.0)]),
+        significance: 0.05,
+        tolerance_pct: 10.0,
+    }
+}
+
+#[test]
+fn test_balanced_distribution_is_ok() {
+    common::setup();
+    let report = check_balance(&[file(&[("email", 50), ("https", 50)])], &half_and_half()).unwrap();
+    let balance = &report.files[0];
+    assert_eq!(balance.chi_square, 0.0);
+    assert_eq!(balance.p_value, 1.0);
+    assert!(!balance.significant);
+    assert_eq!(report.flagged(), 0);
+}
+
+#[test]
+fn test_skewed_distribution_flags_over_and_under() {
+    common::setup();
+    let report = check_balance(&[file(&[("email", 60), ("https", 40)])], &half_and_half()).unwrap();
+    let balance = &report.files[0];
+    assert!((balance.chi_square - 4.0).abs() < 1e-9);
+    assert_eq!(balance.degrees_of_freedom, 1);
+    // P(chi2(1) > 4) = 0.0455
+    assert!((balance.p_value - 0.0455).abs() < 1e-4, "p = {}", balance.p_value);
+
+    let statuses: Vec<_> = balance.distributors.iter().map(|d| (d.distributor.as_str(), d.status)).collect();
+    assert_eq!(statuses, [("email", Provisioning::Over), ("https", Provisioning::Under)]);
+}
+
+#[test]
+fn test_unconfigured_distributor_is_unexpected() {
+    common::setup();
+    let report = check_balance(&[file(&[("email", 5), ("https", 5), ("moat", 2)])], &half_and_half()).unwrap();
+    let balance = &report.files[0];
+    assert_eq!(balance.total, 10);
+    let moat = balance.distributors.iter().find(|d| d.distributor == "moat").unwrap();
+    assert_eq!(moat.status, Provisioning::Unexpected);
+    assert_eq!(report.flagged(), 1);
+}
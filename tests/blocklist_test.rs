@@ -0,0 +1,58 @@
+//! Tests for blocklist country validation and the censorship analysis
+
+use bridge_parser::analysis::{analyze_blocklist, Window};
+use bridge_parser::helper::{is_country_code, split_country_codes};
+use bridge_parser::transformer::parser::parse_line;
+
+mod common;
+
+const A: &str = "00000000000000000000000000000000000000aa";
+const B: &str = "00000000000000000000000000000000000000bb";
+
+#[test]
+fn test_country_code_validation() {
+    common::setup();
+    assert!(is_country_code("ru"));
+    assert!(is_country_code("IR"));
+    assert!(!is_country_code("xx"));
+    assert!(!is_country_code("rus"));
+
+    let (valid, invalid) = split_country_codes("ru,IR,,none,zz,ru");
+    assert_eq!(valid, ["ru", "ir"]);
+    assert_eq!(invalid, ["zz"]);
+}
+
+#[test]
+fn test_blocked_countries_from_line() {
+    common::setup();
+    let entry = parse_line(&format!("{} email transport=obfs4 blocklist=ru,cn,qq", A)).unwrap();
+    assert_eq!(entry.blocklist.as_deref(), Some("ru,cn,qq"));
+    assert_eq!(entry.blocked_countries(), ["ru", "cn"]);
+}
+
+#[test]
+fn test_blocklist_analysis_counts_and_new_blocks() {
+    common::setup();
+    let data = vec![
+        common::assignment("2022-04-09 00:00:00", &[
+            format!("{} email transport=obfs4 blocklist=ru", A),
+            format!("{} https transport=obfs4", B),
+        ]),
+        common::assignment("2022-04-10 00:00:00", &[
+            format!("{} email transport=obfs4 blocklist=ru,ir", A),
+            format!("{} https transport=obfs4 blocklist=ru", B),
+        ]),
+    ];
+
+    let report = analyze_blocklist(&data, Window::Monthly).unwrap();
+    // One monthly window, counted from the latest snapshot
+    let counts: Vec<_> = report.blocked.iter()
+        .map(|b| (b.country.as_str(), b.distributor.as_str(), b.blocked))
+        .collect();
+    assert_eq!(counts, [("ir", "email", 1), ("ru", "email", 1), ("ru", "https", 1)]);
+
+    let newly: Vec<_> = report.newly_blocked.iter()
+        .map(|n| (n.fingerprint.as_str(), n.country.as_str()))
+        .collect();
+    assert_eq!(newly, [(A, "ir"), (B, "ru")]);
+}
@@ -1,4 +1,6 @@
+use std::path::PathBuf;
 use std::sync::Once;
+use bridge_parser::transformer::BridgeParsedAssignment;
 
 static INIT: Once = Once::new();
 
@@ -10,3 +12,41 @@ pub fn setup() {
             .ok(); // Ignore if already initialized
     });
 }
+
+/// Build a parsed assignment from a publication time and raw entry lines.
+#[allow(dead_code)]
+pub fn assignment<S: AsRef<str>>(published: &str, lines: &[S]) -> BridgeParsedAssignment {
+    let content = format!("bridge-pool-assignment {}\n{}\n", published,
+        lines.iter().map(AsRef::as_ref).collect::<Vec<_>>().join("\n"));
+    let raw = bridge_parser::collector::BridgeRawFile {
+        path: String::new(),
+        timestamp: 0,
+        raw: content.as_bytes().to_vec(),
+        content,
+    };
+    bridge_parser::parse_files(vec![raw])
+        .expect("test assignment should parse")
+        .remove(0)
+}
+
+/// Two snapshots a day apart: email, https and moat bridges between them
+/// covering every optional field, then only the email bridge.
+#[allow(dead_code)]
+pub fn sample() -> Vec<BridgeParsedAssignment> {
+    vec![
+        assignment("2022-04-09 00:29:37", &[
+            "0004f8aea55fe852194674c8554d68cc5e7a5bba email transport=vanilla ip=4,6 distributed=true state=functional",
+            "00e1ae6cb75e47e363e6aef9f67a49c0e854fde7 https transport=obfs4 ip=6 bandwidth=accepted ratio=1.49",
+            "0102f3a1b2c3d4e5f60718293a4b5c6d7e8f9012 moat transport=obfs4 blocklist=ru,cn",
+        ]),
+        assignment("2022-04-10 00:29:37", &[
+            "0004f8aea55fe852194674c8554d68cc5e7a5bba email transport=vanilla ip=4,6 distributed=true state=functional",
+        ]),
+    ]
+}
+
+/// `name` in the temp directory, prefixed with this test process's id.
+#[allow(dead_code)]
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("bridge_parser_{}_{}", std::process::id(), name))
+}
@@ -0,0 +1,463 @@
+//! Tests for the file exporters: the shared column schema, CSV and Parquet output,
+//! Parquet row groups and datasets, Arrow IPC, fan-out, existing outputs and compression
+
+use std::path::PathBuf;
+use bridge_parser::exporter::{Column, ColumnSchema, CsvExporter, Exporter, QuoteStyle, TimestampFormat};
+
+mod common;
+
+#[test]
+fn test_default_csv_header_matches_rows() {
+    common::setup();
+    let path = common::temp_path("default.csv");
+    CsvExporter::new(path.clone()).export(&common::sample()).unwrap();
+
+    let mut reader = csv::Reader::from_path(&path).unwrap();
+    let header = reader.headers().unwrap().clone();
+    assert_eq!(&header[0], "file_sha");
+    assert_eq!(&header[1], "published_timestamp");
+    assert_eq!(header.len(), ColumnSchema::default().columns.len());
+
+    let rows: Vec<_> = reader.records().map(Result::unwrap).collect();
+    assert_eq!(rows.len(), 4);
+    for row in &rows {
+        assert_eq!(row.len(), header.len());
+    }
+    let published: Vec<_> = rows.iter().map(|r| &r[1]).collect();
+    assert_eq!(published, ["1649464177000", "1649464177000", "1649464177000", "1649550577000"]);
+    assert_eq!(&rows[0][6], "4,6");
+}
+
+#[test]
+fn test_selected_columns_delimiter_and_iso_timestamps() {
+    common::setup();
+    let path = common::temp_path("selected.csv");
+    let exporter = CsvExporter {
+        schema: ColumnSchema::parse("fingerprint, published, method, ratio", TimestampFormat::Iso8601).unwrap(),
+        delimiter: b';',
+        quote_style: QuoteStyle::Always,
+        ..CsvExporter::new(path.clone())
+    };
+    exporter.export(&common::sample()).unwrap();
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<_> = text.lines().collect();
+    assert_eq!(lines[0], r#""fingerprint";"published_timestamp";"distribution_method";"ratio""#);
+    assert_eq!(
+        lines[2],
+        r#""00e1ae6cb75e47e363e6aef9f67a49c0e854fde7";"2022-04-09T00:29:37Z";"https";"1.49""#
+    );
+}
+
+#[test]
+fn test_column_list_validation() {
+    common::setup();
+    assert_eq!("sha".parse::<Column>().unwrap(), Column::EntrySha);
+    assert!(ColumnSchema::parse("fingerprint,nope", TimestampFormat::default()).is_err());
+    assert!(ColumnSchema::parse("fingerprint,fingerprint", TimestampFormat::default()).is_err());
+    assert!(ColumnSchema::parse(" , ", TimestampFormat::default()).is_err());
+}
+
+#[cfg(feature = "parquet_export")]
+#[test]
+fn test_parquet_uses_column_schema() {
+    use bridge_parser::exporter::ParquetExporter;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    common::setup();
+    let path = common::temp_path("selected.parquet");
+    let exporter = ParquetExporter {
+        output_path: path.clone(),
+        schema: ColumnSchema::parse("file_sha,published,fingerprint,distributed", TimestampFormat::EpochSeconds).unwrap(),
+        ..ParquetExporter::new(path.clone())
+    };
+    exporter.export(&common::sample()).unwrap();
+
+    let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+    let metadata = reader.metadata();
+    assert_eq!(metadata.file_metadata().num_rows(), 4);
+    let names: Vec<_> = metadata.file_metadata().schema_descr().columns().iter().map(|c| c.name().to_string()).collect();
+    assert_eq!(names, ["file_sha", "published_timestamp", "fingerprint", "distributed"]);
+}
+
+#[cfg(feature = "parquet_export")]
+#[test]
+fn test_parquet_compression_parsing() {
+    use bridge_parser::exporter::ParquetCompression;
+
+    assert_eq!("zstd".parse::<ParquetCompression>().unwrap(), ParquetCompression::Zstd(None));
+    assert_eq!("ZSTD:9".parse::<ParquetCompression>().unwrap(), ParquetCompression::Zstd(Some(9)));
+    assert_eq!("gzip:6".parse::<ParquetCompression>().unwrap(), ParquetCompression::Gzip(Some(6)));
+    assert_eq!("none".parse::<ParquetCompression>().unwrap(), ParquetCompression::None);
+    assert_eq!(ParquetCompression::default(), ParquetCompression::Snappy);
+    assert!("snappy:1".parse::<ParquetCompression>().is_err());
+    assert!("zstd:max".parse::<ParquetCompression>().is_err());
+    assert!("lzo".parse::<ParquetCompression>().is_err());
+}
+
+#[cfg(feature = "parquet_export")]
+#[test]
+fn test_parquet_row_groups_encoding_and_bloom_filter() {
+    use bridge_parser::exporter::{ParquetCompression, ParquetExporter};
+    use parquet::basic::Compression;
+    use parquet::data_type::ByteArray;
+    use parquet::file::properties::ReaderProperties;
+    use parquet::file::reader::FileReader;
+    use parquet::file::serialized_reader::{ReadOptionsBuilder, SerializedFileReader};
+
+    common::setup();
+    let path = common::temp_path("row_groups.parquet");
+    let exporter = ParquetExporter {
+        row_group_size: 2,
+        compression: ParquetCompression::Zstd(Some(3)),
+        ..ParquetExporter::new(path.clone())
+    };
+    let data = common::sample();
+    exporter.export(&data).unwrap();
+
+    let options = ReadOptionsBuilder::new()
+        .with_reader_properties(ReaderProperties::builder().set_read_bloom_filter(true).build())
+        .build();
+    let reader = SerializedFileReader::new_with_options(std::fs::File::open(&path).unwrap(), options).unwrap();
+    let metadata = reader.metadata();
+    assert_eq!(metadata.file_metadata().num_rows(), 4);
+    assert_eq!(metadata.num_row_groups(), 2);
+
+    let columns = metadata.file_metadata().schema_descr();
+    let index = |name: &str| columns.columns().iter().position(|c| c.name() == name).unwrap();
+    let group = metadata.row_group(0);
+    for chunk in group.columns() {
+        assert!(matches!(chunk.compression(), Compression::ZSTD(_)), "{}", chunk.column_path());
+        assert!(chunk.statistics().is_some(), "{}", chunk.column_path());
+    }
+    assert!(group.column(index("distribution_method")).dictionary_page_offset().is_some());
+    assert!(group.column(index("fingerprint")).dictionary_page_offset().is_none());
+
+    let row_group = reader.get_row_group(0).unwrap();
+    let bloom = row_group.get_column_bloom_filter(index("fingerprint")).expect("fingerprint bloom filter");
+    assert!(bloom.check(&ByteArray::from("0004f8aea55fe852194674c8554d68cc5e7a5bba")));
+    assert!(!bloom.check(&ByteArray::from("ffffffffffffffffffffffffffffffffffffffff")));
+    assert!(row_group.get_column_bloom_filter(index("transport")).is_none());
+}
+
+#[cfg(feature = "parquet_export")]
+#[test]
+fn test_parquet_logical_types_and_metadata() {
+    use arrow::array::{Array, AsArray, Float32Array, ListArray, TimestampMillisecondArray, UInt8Array};
+    use arrow::datatypes::{DataType, TimeUnit};
+    use bridge_parser::exporter::ParquetExporter;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    common::setup();
+    let path = common::temp_path("logical_types.parquet");
+    let exporter = ParquetExporter {
+        source: Some("https://collector.torproject.org/recent/bridge-pool-assignments".to_string()),
+        ..ParquetExporter::new(path.clone())
+    };
+    let data = vec![common::assignment("2022-04-09 00:29:37", &[
+        "0004f8aea55fe852194674c8554d68cc5e7a5bba moat transport=obfs4 ip=4,6 blocklist=ru,cn distributed=true ratio=0.5",
+        "00e1ae6cb75e47e363e6aef9f67a49c0e854fde7 https transport=vanilla",
+    ])];
+    exporter.export(&data).unwrap();
+
+    let builder = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path).unwrap()).unwrap();
+    let schema = builder.schema().clone();
+    let data_type = |name: &str| schema.field_with_name(name).unwrap().data_type().clone();
+    assert_eq!(data_type("published_timestamp"), DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())));
+    assert_eq!(data_type("ratio"), DataType::Float32);
+    assert_eq!(data_type("distributed"), DataType::Boolean);
+    assert_eq!(data_type("transport"), DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)));
+    assert!(matches!(data_type("ip"), DataType::List(f) if f.data_type() == &DataType::UInt8));
+    assert!(matches!(data_type("blocklist"), DataType::List(f) if f.data_type() == &DataType::Utf8));
+    assert_eq!(schema.metadata()["bridge_parser.version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(
+        schema.metadata()["bridge_parser.source"],
+        "https://collector.torproject.org/recent/bridge-pool-assignments"
+    );
+    assert!(builder.metadata().file_metadata().created_by().unwrap().starts_with("bridge-parser version"));
+
+    let batch = builder.build().unwrap().next().unwrap().unwrap();
+    let column = |name: &str| batch.column(schema.index_of(name).unwrap()).clone();
+    let published = column("published_timestamp");
+    let published = published.as_any().downcast_ref::<TimestampMillisecondArray>().unwrap();
+    assert_eq!(published.value(0), data[0].published);
+
+    let ip = column("ip");
+    let ip = ip.as_any().downcast_ref::<ListArray>().unwrap();
+    assert_eq!(ip.value(0).as_any().downcast_ref::<UInt8Array>().unwrap().values(), &[4, 6]);
+    assert!(ip.is_null(1));
+
+    let blocklist = column("blocklist");
+    let blocklist = blocklist.as_any().downcast_ref::<ListArray>().unwrap();
+    let countries: Vec<_> = blocklist.value(0).as_string::<i32>().iter().flatten().map(str::to_string).collect();
+    assert_eq!(countries, ["ru", "cn"]);
+    assert!(blocklist.is_null(1));
+
+    let ratio = column("ratio");
+    let ratio = ratio.as_any().downcast_ref::<Float32Array>().unwrap();
+    assert_eq!(ratio.value(0), 0.5);
+    assert!(ratio.is_null(1));
+}
+
+#[cfg(feature = "parquet_export")]
+#[test]
+fn test_parquet_dataset_appends_partitions_idempotently() {
+    use bridge_parser::exporter::{DatasetManifest, ParquetExporter};
+
+    common::setup();
+    let root = common::temp_path("dataset");
+    let _ = std::fs::remove_dir_all(&root);
+    let exporter = ParquetExporter { dataset: true, ..ParquetExporter::new(root.clone()) };
+    let parts = |month: &str| {
+        let mut names: Vec<_> = std::fs::read_dir(root.join("year=2022").join(month))
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    };
+
+    let mut data = common::sample();
+    data.push(common::assignment("2022-05-02 10:00:00", &[
+        "0004f8aea55fe852194674c8554d68cc5e7a5bba email transport=vanilla",
+    ]));
+    exporter.export(&data).unwrap();
+    let april = parts("month=04");
+    assert_eq!(april.len(), 1);
+    assert_eq!(parts("month=05").len(), 1);
+    let first = std::fs::read(root.join("year=2022/month=04").join(&april[0])).unwrap();
+
+    // Re-running with one new April file adds a part and leaves the old one alone
+    data.push(common::assignment("2022-04-20 10:00:00", &[
+        "00e1ae6cb75e47e363e6aef9f67a49c0e854fde7 https transport=obfs4",
+    ]));
+    exporter.export(&data).unwrap();
+    assert_eq!(parts("month=04").len(), 2);
+    assert_eq!(parts("month=05").len(), 1);
+    assert_eq!(std::fs::read(root.join("year=2022/month=04").join(&april[0])).unwrap(), first);
+
+    exporter.export(&data).unwrap();
+    assert_eq!(parts("month=04").len(), 2);
+
+    let manifest = DatasetManifest::load(&root).unwrap();
+    assert_eq!(manifest.partitions.len(), 2);
+    assert_eq!(manifest.partitions["year=2022/month=04"].len(), 2);
+    let shas = manifest.file_shas();
+    assert_eq!(shas.len(), 4);
+    assert!(data.iter().all(|f| shas.contains(f.file_sha.as_str())));
+}
+
+#[cfg(feature = "arrow_export")]
+#[test]
+fn test_arrow_ipc_file_and_stream_round_trip() {
+    use arrow::datatypes::DataType;
+    use arrow::ipc::reader::{FileReader, StreamReader};
+    use bridge_parser::exporter::{ArrowIpcExporter, IpcFormat};
+
+    common::setup();
+    assert_eq!("Stream".parse::<IpcFormat>().unwrap(), IpcFormat::Stream);
+    assert_eq!(IpcFormat::default(), IpcFormat::File);
+    assert!("feather".parse::<IpcFormat>().is_err());
+
+    let data = common::sample();
+
+    // Batches of two give the file a second batch with different categorical values
+    let path = common::temp_path("entries.arrow");
+    ArrowIpcExporter { batch_size: 2, ..ArrowIpcExporter::new(path.clone()) }.export(&data).unwrap();
+    let reader = FileReader::try_new(std::fs::File::open(&path).unwrap(), None).unwrap();
+    let schema = reader.schema();
+    assert_eq!(schema.field_with_name("transport").unwrap().data_type(), &DataType::Utf8);
+    assert_eq!(schema.metadata()["bridge_parser.version"], env!("CARGO_PKG_VERSION"));
+    let batches: Vec<_> = reader.map(Result::unwrap).collect();
+    assert_eq!(batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>(), [2, 2]);
+
+    let path = common::temp_path("entries.arrows");
+    let exporter = ArrowIpcExporter {
+        format: IpcFormat::Stream,
+        batch_size: 2,
+        ..ArrowIpcExporter::new(path.clone())
+    };
+    exporter.export(&data).unwrap();
+    let reader = StreamReader::try_new(std::fs::File::open(&path).unwrap(), None).unwrap();
+    assert_eq!(
+        reader.schema().field_with_name("transport").unwrap().data_type(),
+        &DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8))
+    );
+    let rows: usize = reader.map(|b| b.unwrap().num_rows()).sum();
+    assert_eq!(rows, 4);
+}
+
+#[test]
+fn test_output_spec_and_failure_policy_parsing() {
+    use bridge_parser::exporter::{FailurePolicy, OutputSpec};
+
+    let spec: OutputSpec = "Parquet=entries.parquet".parse().unwrap();
+    assert_eq!(spec.format, "parquet");
+    assert_eq!(spec.path_or("default.parquet"), PathBuf::from("entries.parquet"));
+    assert_eq!(spec.to_string(), "parquet=entries.parquet");
+
+    // Only the first '=' separates the target, and connection strings are not shown
+    let spec: OutputSpec = "postgres=host=db password=secret".parse().unwrap();
+    assert_eq!(spec.target.as_deref(), Some("host=db password=secret"));
+    assert_eq!(spec.to_string(), "postgres");
+
+    let spec: OutputSpec = "csv".parse().unwrap();
+    assert_eq!(spec.path_or("output.csv"), PathBuf::from("output.csv"));
+    assert!("=out.csv".parse::<OutputSpec>().is_err());
+    assert!("csv=".parse::<OutputSpec>().is_err());
+
+    // Options follow the target after ';' and only split at their first '='
+    let spec: OutputSpec = "csv=stats.csv; Columns=fingerprint,method ;compression=gzip".parse().unwrap();
+    assert_eq!(spec.path_or("output.csv"), PathBuf::from("stats.csv"));
+    assert_eq!(spec.options, vec![
+        ("columns".to_string(), "fingerprint,method".to_string()),
+        ("compression".to_string(), "gzip".to_string()),
+    ]);
+    assert_eq!(spec.to_string(), "csv=stats.csv;columns=fingerprint,method;compression=gzip");
+    let spec: OutputSpec = "postgres=host=db;pg-mode=upsert".parse().unwrap();
+    assert_eq!(spec.target.as_deref(), Some("host=db"));
+    assert_eq!(spec.to_string(), "postgres;pg-mode=upsert");
+    let spec: OutputSpec = "parquet;compression=zstd:3".parse().unwrap();
+    assert_eq!(spec.target, None);
+    assert_eq!(spec.options, vec![("compression".to_string(), "zstd:3".to_string())]);
+    assert!("csv=out.csv;gzip".parse::<OutputSpec>().is_err());
+    assert!("csv=out.csv;=gzip".parse::<OutputSpec>().is_err());
+
+    assert_eq!("ABORT".parse::<FailurePolicy>().unwrap(), FailurePolicy::Abort);
+    assert_eq!(FailurePolicy::default(), FailurePolicy::Continue);
+    assert!("retry".parse::<FailurePolicy>().is_err());
+}
+
+#[test]
+fn test_fan_out_reports_each_output_and_applies_policy() {
+    use bridge_parser::error::BridgeError;
+    use bridge_parser::exporter::{FailurePolicy, FanOutExporter, OutputStatus};
+    use bridge_parser::transformer::BridgeParsedAssignment;
+
+    struct Failing;
+    impl Exporter for Failing {
+        fn export(&self, _data: &[BridgeParsedAssignment]) -> Result<(), BridgeError> {
+            Err(BridgeError::Database("connection refused".into()))
+        }
+    }
+
+    common::setup();
+    let first = common::temp_path("fanout_first.csv");
+    let second = common::temp_path("fanout_second.csv");
+    let _ = std::fs::remove_file(&second);
+    let fanout = |policy| FanOutExporter {
+        outputs: vec![
+            ("csv=first".to_string(), Box::new(CsvExporter::new(first.clone())) as Box<dyn Exporter>),
+            ("postgres".to_string(), Box::new(Failing)),
+            ("csv=second".to_string(), Box::new(CsvExporter::new(second.clone()))),
+        ],
+        policy,
+    };
+
+    let reports = fanout(FailurePolicy::Continue).run(&common::sample());
+    assert!(matches!(reports[0].status, OutputStatus::Succeeded));
+    assert!(matches!(reports[1].status, OutputStatus::Failed(BridgeError::Database(_))));
+    assert!(matches!(reports[2].status, OutputStatus::Succeeded));
+    assert!(first.exists() && second.exists());
+
+    std::fs::remove_file(&second).unwrap();
+    let reports = fanout(FailurePolicy::Abort).run(&common::sample());
+    assert_eq!(reports.iter().map(|r| r.name.as_str()).collect::<Vec<_>>(), ["csv=first", "postgres", "csv=second"]);
+    assert!(matches!(reports[2].status, OutputStatus::Skipped));
+    assert!(!second.exists());
+
+    let err = fanout(FailurePolicy::Abort).export(&common::sample()).unwrap_err().to_string();
+    assert!(err.contains("1 of 3 outputs failed (postgres), 1 skipped"), "{}", err);
+}
+
+#[test]
+fn test_if_exists_refuses_or_versions_existing_outputs() {
+    use bridge_parser::exporter::IfExists;
+
+    common::setup();
+    assert_eq!("Version".parse::<IfExists>().unwrap(), IfExists::Version);
+    assert_eq!(IfExists::default(), IfExists::Overwrite);
+    assert!("append".parse::<IfExists>().is_err());
+
+    let path = common::temp_path("existing.csv");
+    let first = common::temp_path("existing.1.csv");
+    let second = common::temp_path("existing.2.csv");
+    for p in [&first, &second] {
+        let _ = std::fs::remove_file(p);
+    }
+    std::fs::write(&path, "keep me\n").unwrap();
+    let exporter = |if_exists| CsvExporter { if_exists, ..CsvExporter::new(path.clone()) };
+
+    let err = exporter(IfExists::Refuse).export(&common::sample()).unwrap_err().to_string();
+    assert!(err.contains("already exists"), "{}", err);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me\n");
+
+    exporter(IfExists::Version).export(&common::sample()).unwrap();
+    exporter(IfExists::Version).export(&common::sample()).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me\n");
+    assert_eq!(csv::Reader::from_path(&first).unwrap().records().count(), 4);
+    assert_eq!(std::fs::read(&first).unwrap(), std::fs::read(&second).unwrap());
+
+    exporter(IfExists::Overwrite).export(&common::sample()).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), std::fs::read(&first).unwrap());
+}
+
+#[test]
+fn test_csv_compression_by_extension_or_flag() {
+    use std::io::Read;
+    use bridge_parser::exporter::{Compression, IfExists};
+
+    common::setup();
+    let plain = common::temp_path("compressed.csv");
+    CsvExporter::new(plain.clone()).export(&common::sample()).unwrap();
+    let expected = std::fs::read_to_string(&plain).unwrap();
+
+    let decompress = |path: &PathBuf, codec: Compression| {
+        let file = std::fs::File::open(path).unwrap();
+        let mut text = String::new();
+        match codec {
+            Compression::Gzip => flate2::read::GzDecoder::new(file).read_to_string(&mut text),
+            Compression::Xz => xz2::read::XzDecoder::new(file).read_to_string(&mut text),
+            Compression::Zstd => zstd::Decoder::new(file).unwrap().read_to_string(&mut text),
+            other => panic!("{:?} is not a codec", other),
+        }
+        .unwrap();
+        text
+    };
+
+    for (name, codec) in [
+        ("compressed.csv.gz", Compression::Gzip),
+        ("compressed.csv.xz", Compression::Xz),
+        ("compressed.csv.zst", Compression::Zstd),
+    ] {
+        let path = common::temp_path(name);
+        CsvExporter::new(path.clone()).export(&common::sample()).unwrap();
+        assert_eq!(decompress(&path, codec), expected, "{}", name);
+    }
+
+    // An explicit codec wins over the extension, at the requested level
+    let path = common::temp_path("flagged.csv");
+    let exporter = CsvExporter {
+        compression: Compression::Xz,
+        compression_level: Some(9),
+        ..CsvExporter::new(path.clone())
+    };
+    exporter.export(&common::sample()).unwrap();
+    assert_eq!(decompress(&path, Compression::Xz), expected);
+
+    let invalid = CsvExporter {
+        compression: Compression::Gzip,
+        compression_level: Some(12),
+        ..CsvExporter::new(path.clone())
+    };
+    let err = invalid.export(&common::sample()).unwrap_err().to_string();
+    assert!(err.contains("compression level 12"), "{}", err);
+    assert!("lz4".parse::<Compression>().is_err());
+
+    // Versions keep the compression extension last
+    let versioned = common::temp_path("compressed.1.csv.gz");
+    let _ = std::fs::remove_file(&versioned);
+    let exporter = CsvExporter { if_exists: IfExists::Version, ..CsvExporter::new(common::temp_path("compressed.csv.gz")) };
+    exporter.export(&common::sample()).unwrap();
+    assert_eq!(decompress(&versioned, Compression::Gzip), expected);
+}
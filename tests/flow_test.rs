@@ -0,0 +1,76 @@
+//! Tests for the distributor flow (Sankey) export
+
+use chrono::NaiveDate;
+use bridge_parser::analysis::analyze_flows;
+use bridge_parser::analysis::flow::{ENTERED_POOL, LEFT_POOL};
+use bridge_parser::exporter::IfExists;
+
+mod common;
+
+const A: &str = "00000000000000000000000000000000000000aa";
+const B: &str = "00000000000000000000000000000000000000bb";
+const C: &str = "00000000000000000000000000000000000000cc";
+
+fn snapshots() -> Vec<bridge_parser::transformer::BridgeParsedAssignment> {
+    vec![
+        common::assignment("2022-04-09 00:00:00", &[format!("{} email", A), format!("{} https", B)]),
+        common::assignment("2022-04-10 00:00:00", &[format!("{} moat", A), format!("{} https", C)]),
+        common::assignment("2022-04-11 00:00:00", &[format!("{} moat", A)]),
+    ]
+}
+
+#[test]
+fn test_consecutive_flows_include_pool_pseudo_nodes() {
+    common::setup();
+    let report = analyze_flows(&snapshots(), None).unwrap();
+    assert_eq!(report.stages.len(), 3);
+
+    let first: Vec<_> = report.edges.iter()
+        .filter(|e| e.step == 0)
+        .map(|e| (e.source.as_str(), e.target.as_str(), e.bridges))
+        .collect();
+    assert_eq!(first, [("email", "moat", 1), (ENTERED_POOL, "https", 1), ("https", LEFT_POOL, 1)]);
+}
+
+#[test]
+fn test_flows_between_dates_use_outer_snapshots() {
+    common::setup();
+    let from = NaiveDate::from_ymd_opt(2022, 4, 9).unwrap();
+    let to = NaiveDate::from_ymd_opt(2022, 4, 11).unwrap();
+    let report = analyze_flows(&snapshots(), Some((from, to))).unwrap();
+    assert_eq!(report.stages.len(), 2);
+    let edges: Vec<_> = report.edges.iter().map(|e| (e.source.as_str(), e.target.as_str())).collect();
+    assert_eq!(edges, [("email", "moat"), ("https", LEFT_POOL)]);
+
+    assert!(analyze_flows(&snapshots(), Some((to, to))).is_err());
+}
+
+#[test]
+fn test_sankey_nodes_are_per_stage() {
+    common::setup();
+    let sankey = analyze_flows(&snapshots(), None).unwrap().to_sankey();
+    // "moat" appears at stage 1 and stage 2 as separate nodes, so there are no cycles
+    let moat: Vec<_> = sankey.nodes.iter().filter(|n| n.name == "moat").map(|n| n.stage).collect();
+    assert_eq!(moat, [1, 2]);
+    for link in &sankey.links {
+        assert_eq!(sankey.nodes[link.target].stage, sankey.nodes[link.source].stage + 1);
+    }
+
+    let json = serde_json::to_value(&sankey).unwrap();
+    assert!(json["nodes"].is_array() && json["links"][0]["value"].is_u64());
+}
+
+#[test]
+fn test_sankey_json_file_respects_if_exists() {
+    common::setup();
+    let report = analyze_flows(&snapshots(), None).unwrap();
+    let path = common::temp_path("flows.json");
+    let _ = std::fs::remove_file(&path);
+
+    report.write_sankey_json(&path, IfExists::Refuse).unwrap();
+    let written: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(written, serde_json::to_value(report.to_sankey()).unwrap());
+
+    let err = report.write_sankey_json(&path, IfExists::Refuse).unwrap_err().to_string();
+    assert!(err.contains("already exists"), "{}", err);
+}
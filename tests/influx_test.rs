@@ -0,0 +1,112 @@
+//! Tests for the InfluxDB line-protocol exporter, against a local stub write endpoint
+
+use std::sync::{Arc, Mutex};
+use bridge_parser::exporter::{Exporter, InfluxExporter};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+mod common;
+
+/// Requests received by the stub: (head, body).
+type Received = Arc<Mutex<Vec<(String, String)>>>;
+
+/// Serve `status` to every request on a local port; returns the write URL.
+async fn stub(status: &'static str) -> (String, Received) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/api/v2/write?org=tor&bucket=bridges", listener.local_addr().unwrap());
+    let received = Received::default();
+    let log = received.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            let (head, body) = loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request).into_owned();
+                if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                    let length: usize = head
+                        .to_ascii_lowercase()
+                        .lines()
+                        .find_map(|l| l.strip_prefix("content-length:").map(|v| v.trim().parse().unwrap()))
+                        .unwrap_or(0);
+                    if body.len() >= length {
+                        break (head.to_string(), body.to_string());
+                    }
+                }
+            };
+            log.lock().unwrap().push((head, body));
+            let response = format!("HTTP/1.1 {}\r\ncontent-length: 5\r\nconnection: close\r\n\r\nstub\n", status);
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+    (url, received)
+}
+
+#[test]
+fn test_influx_lines_per_file() {
+    common::setup();
+    let path = common::temp_path("aggregates.lp");
+    let data = vec![
+        common::assignment("2022-04-09 00:29:37", &[
+            "0004f8aea55fe852194674c8554d68cc5e7a5bba https transport=obfs4 state=functional ratio=1.5",
+            "00e1ae6cb75e47e363e6aef9f67a49c0e854fde7 https transport=obfs4 state=functional ratio=0.5",
+            "01bd7a8b8c7b8c7d8e3f6a4b2c1d0e9f8a7b6c5d moat ratio=4",
+            "02c4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d2 moat ratio=NaN",
+            "03d5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d2e3 moat ratio=inf",
+        ]),
+        common::assignment("2022-04-10 00:00:00", &[
+            "0004f8aea55fe852194674c8554d68cc5e7a5bba email transport=vanilla",
+        ]),
+    ];
+    InfluxExporter::new(Some(path.clone())).export(&data).unwrap();
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(text.lines().collect::<Vec<_>>(), [
+        "bridge_pool,method=https,transport=obfs4,state=functional bridges=2i 1649464177000000000",
+        "bridge_pool,method=moat,transport=unknown,state=unknown bridges=3i 1649464177000000000",
+        "bridge_ratio count=3i,min=0.5,max=4,mean=2,median=1.5 1649464177000000000",
+        "bridge_pool,method=email,transport=vanilla,state=unknown bridges=1i 1649548800000000000",
+    ]);
+
+    let err = InfluxExporter::new(None).export(&common::sample()).unwrap_err().to_string();
+    assert!(err.contains("output path or a write URL"), "{}", err);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_influx_posts_batches_to_write_endpoint() {
+    common::setup();
+    let (url, received) = stub("204 No Content").await;
+    let exporter = InfluxExporter {
+        write_url: Some(url),
+        token: Some("secret".into()),
+        batch_size: 3,
+        ..InfluxExporter::new(None)
+    };
+    exporter.export(&common::sample()).unwrap();
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 2);
+    let (head, _) = &received[0];
+    assert!(head.starts_with("POST /api/v2/write?org=tor&bucket=bridges HTTP/1.1"), "{}", head);
+    assert!(head.to_ascii_lowercase().contains("authorization: token secret"), "{}", head);
+    assert_eq!(received[0].1.lines().count(), 3);
+    let rest: Vec<&str> = received[1].1.lines().collect();
+    assert_eq!(rest.len(), 2);
+    assert!(rest[0].starts_with("bridge_ratio count=1i,min=1.49,max=1.49,"), "{}", rest[0]);
+    assert_eq!(rest[1], "bridge_pool,method=email,transport=vanilla,state=functional bridges=1i 1649550577000000000");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_influx_write_error_discards_file_output() {
+    common::setup();
+    let (url, _) = stub("400 Bad Request").await;
+    let path = common::temp_path("rejected.lp");
+    let _ = std::fs::remove_file(&path);
+    let exporter = InfluxExporter { write_url: Some(url), ..InfluxExporter::new(Some(path.clone())) };
+
+    let err = exporter.export(&common::sample()).unwrap_err().to_string();
+    assert!(err.contains("400 Bad Request stub"), "{}", err);
+    assert!(!path.exists());
+}
@@ -0,0 +1,97 @@
+//! Tests for the JSON and NDJSON exporters
+
+use bridge_parser::exporter::{Exporter, JsonExporter, NdjsonExporter};
+use bridge_parser::transformer::BridgeParsedAssignment;
+
+mod common;
+
+#[test]
+fn test_json_round_trips_nested_structure() {
+    common::setup();
+    for pretty in [false, true] {
+        let path = common::temp_path(&format!("nested_{}.json", pretty));
+        JsonExporter { pretty, ..JsonExporter::new(path.clone()) }.export(&common::sample()).unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().count() > 1, pretty);
+        let back: Vec<BridgeParsedAssignment> = serde_json::from_str(&text).unwrap();
+        assert_eq!(back.len(), 2);
+        assert_eq!(back[0].file_sha, common::sample()[0].file_sha);
+        assert_eq!(back[0].lines.len(), 3);
+        assert_eq!(back[0].lines[1].ratio, Some(1.49));
+    }
+}
+
+#[test]
+fn test_ndjson_writes_one_flat_entry_per_line() {
+    common::setup();
+    let path = common::temp_path("flat.ndjson");
+    NdjsonExporter::new(path.clone()).export(&common::sample()).unwrap();
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    let records: Vec<serde_json::Value> = text.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(records.len(), 4);
+    assert_eq!(records[0]["published"], 1649464177000i64);
+    assert_eq!(records[0]["file_sha"], common::sample()[0].file_sha.as_str());
+    assert_eq!(records[0]["fingerprint"], "0004f8aea55fe852194674c8554d68cc5e7a5bba");
+    assert!(records[0]["ratio"].is_null());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_streamed_json_matches_synchronous_export() {
+    use bridge_parser::exporter::{export_stream, AssignmentWriter, StreamingExporter};
+
+    common::setup();
+    let data = common::sample();
+    for pretty in [false, true] {
+        let streamed = common::temp_path(&format!("streamed_{}.json", pretty));
+        let exporter = JsonExporter { pretty, ..JsonExporter::new(streamed.clone()) };
+        let mut writer = exporter.begin().await.unwrap();
+        for assignment in &data {
+            writer.write_assignment(assignment).await.unwrap();
+        }
+        writer.finish().await.unwrap();
+
+        // The synchronous adapter runs inside a multi-threaded runtime too
+        let batch = common::temp_path(&format!("batch_{}.json", pretty));
+        JsonExporter { pretty, ..JsonExporter::new(batch.clone()) }.export(&data).unwrap();
+        let text = std::fs::read_to_string(&streamed).unwrap();
+        assert_eq!(text, std::fs::read_to_string(&batch).unwrap());
+        let back: Vec<BridgeParsedAssignment> = serde_json::from_str(&text).unwrap();
+        assert_eq!(back.len(), 2);
+
+        let empty = common::temp_path(&format!("empty_{}.json", pretty));
+        export_stream(&JsonExporter { pretty, ..JsonExporter::new(empty.clone()) }, &[]).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&empty).unwrap(), "[]\n");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_aborted_stream_leaves_existing_output_untouched() {
+    use bridge_parser::exporter::{AssignmentWriter, StreamingExporter};
+
+    common::setup();
+    let path = common::temp_path("aborted.ndjson");
+    std::fs::write(&path, "previous run\n").unwrap();
+    let exporter = NdjsonExporter::new(path.clone());
+    let mut writer = exporter.begin().await.unwrap();
+    writer.write_assignment(&common::sample()[0]).await.unwrap();
+    writer.abort().await.unwrap();
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "previous run\n");
+    let name = path.file_name().unwrap().to_string_lossy().into_owned();
+    let leftovers = std::fs::read_dir(path.parent().unwrap())
+        .unwrap()
+        .filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().starts_with(&format!(".{}", name)))
+        .count();
+    assert_eq!(leftovers, 0);
+}
+
+#[tokio::test]
+async fn test_synchronous_export_inside_current_thread_runtime() {
+    common::setup();
+    let path = common::temp_path("current_thread.json");
+    JsonExporter::new(path.clone()).export(&common::sample()).unwrap();
+    let back: Vec<BridgeParsedAssignment> = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(back[0].lines.len(), 3);
+}
@@ -0,0 +1,507 @@
+//! Tests for the PostgreSQL exporter.
+//!
+//! Tests that need a server are ignored by default; run them with
+//! `BRIDGE_PARSER_TEST_DB=<conn_str> cargo test --test pg_test -- --ignored`.
+//! The TLS test instead needs `BRIDGE_PARSER_TEST_TLS_DB`, a TCP connection string
+//! to `localhost` on a server with TLS enabled, and `BRIDGE_PARSER_TEST_TLS_CA`,
+//! the PEM CA that signed its certificate (issued for `localhost` only).
+
+use bridge_parser::exporter::migrate::{latest_version, MIGRATIONS};
+use bridge_parser::error::BridgeError;
+use bridge_parser::exporter::{
+    ExportMode, Exporter, Layout, LoadMode, OlderThan, PostgresExporter, PruneAction, SslMode, SummaryView,
+    TlsConfig,
+};
+use chrono::NaiveDate;
+use std::sync::Mutex;
+use tokio_postgres::NoTls;
+
+/// Database tests share one server, so they must not run concurrently.
+static DB_LOCK: Mutex<()> = Mutex::new(());
+
+mod common;
+
+fn test_db() -> String {
+    std::env::var("BRIDGE_PARSER_TEST_DB").expect("BRIDGE_PARSER_TEST_DB must be set for database tests")
+}
+
+fn count(conn_str: &str, sql: &str) -> i64 {
+    tokio::runtime::Runtime::new().unwrap().block_on(async {
+        let (client, connection) = tokio_postgres::connect(conn_str, NoTls).await.unwrap();
+        tokio::spawn(connection);
+        client.query_one(sql, &[]).await.unwrap().get(0)
+    })
+}
+
+fn execute(conn_str: &str, sql: &str) {
+    tokio::runtime::Runtime::new().unwrap().block_on(async {
+        let (client, connection) = tokio_postgres::connect(conn_str, NoTls).await.unwrap();
+        tokio::spawn(connection);
+        client.batch_execute(sql).await.unwrap();
+    })
+}
+
+#[test]
+fn test_load_mode_parsing() {
+    assert_eq!("copy".parse::<LoadMode>().unwrap(), LoadMode::Copy);
+    assert_eq!("INSERT".parse::<LoadMode>().unwrap(), LoadMode::Insert);
+    assert_eq!(LoadMode::default(), LoadMode::Copy);
+    assert!("bulk".parse::<LoadMode>().is_err());
+}
+
+#[test]
+fn test_export_mode_parsing() {
+    assert_eq!("skip-existing".parse::<ExportMode>().unwrap(), ExportMode::SkipExisting);
+    assert_eq!("Upsert".parse::<ExportMode>().unwrap(), ExportMode::Upsert);
+    assert_eq!("replace_file".parse::<ExportMode>().unwrap(), ExportMode::ReplaceFile);
+    assert_eq!(ExportMode::default(), ExportMode::Append);
+    assert!("overwrite".parse::<ExportMode>().is_err());
+}
+
+#[test]
+fn test_older_than_parsing() {
+    let today = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+    let cutoff = |s: &str| s.parse::<OlderThan>().unwrap().cutoff(today);
+    assert_eq!(cutoff("2023-01-01"), NaiveDate::from_ymd_opt(2023, 1, 1).unwrap());
+    assert_eq!(cutoff("10d"), NaiveDate::from_ymd_opt(2024, 3, 21).unwrap());
+    assert_eq!(cutoff("2w"), NaiveDate::from_ymd_opt(2024, 3, 17).unwrap());
+    assert_eq!(cutoff("1m"), NaiveDate::from_ymd_opt(2024, 2, 29).unwrap());
+    assert_eq!(cutoff("2y"), NaiveDate::from_ymd_opt(2022, 3, 31).unwrap());
+    for invalid in ["", "m", "12", "3 months", "-1d"] {
+        assert!(invalid.parse::<OlderThan>().is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn test_summary_view_parsing() {
+    assert_eq!("daily".parse::<SummaryView>().unwrap(), SummaryView::Daily);
+    assert_eq!(" Pool".parse::<SummaryView>().unwrap(), SummaryView::Pool);
+    assert_eq!("seen".parse::<SummaryView>().unwrap(), SummaryView::Seen);
+    assert!("weekly".parse::<SummaryView>().is_err());
+}
+
+#[test]
+fn test_sslmode_parsing() {
+    assert_eq!("disable".parse::<SslMode>().unwrap(), SslMode::Disable);
+    assert_eq!("verify-ca".parse::<SslMode>().unwrap(), SslMode::VerifyCa);
+    assert_eq!("Verify-Full".parse::<SslMode>().unwrap(), SslMode::VerifyFull);
+    assert_eq!(SslMode::default(), SslMode::Prefer);
+    assert!("allow".parse::<SslMode>().is_err());
+}
+
+#[test]
+fn test_client_cert_requires_key() {
+    let exporter = PostgresExporter {
+        tls: TlsConfig {
+            mode: Some(SslMode::Require),
+            client_cert: Some("client.crt".into()),
+            ..TlsConfig::default()
+        },
+        ..PostgresExporter::new("host=localhost user=postgres".into())
+    };
+    assert!(matches!(exporter.migration_status(), Err(BridgeError::Config(_))));
+}
+
+#[test]
+fn test_verify_sslmode_in_connection_string() {
+    // tokio-postgres cannot parse these modes itself; nothing listens on port 1
+    for conn_str in [
+        "host=127.0.0.1 port=1 user=postgres sslmode=verify-full",
+        "host=127.0.0.1 port=1 sslmode = 'verify-ca' user=postgres",
+        "postgresql://postgres@127.0.0.1:1/tor?sslmode=verify-full&connect_timeout=5",
+    ] {
+        let err = PostgresExporter::new(conn_str.into()).migration_status().unwrap_err();
+        assert!(matches!(err, BridgeError::Database(_)), "{}: {}", conn_str, err);
+    }
+}
+
+#[test]
+fn test_migrations_are_ordered() {
+    assert!(!MIGRATIONS.is_empty());
+    for (i, migration) in MIGRATIONS.iter().enumerate() {
+        assert_eq!(migration.version, i as i64 + 1, "migration '{}' out of sequence", migration.name);
+        assert!(!migration.sql.trim().is_empty());
+    }
+    assert_eq!(latest_version(), MIGRATIONS.len() as i64);
+}
+
+#[test]
+#[ignore]
+fn test_copy_and_insert_load_the_same_rows() {
+    let _guard = DB_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    let conn_str = test_db();
+
+    for load_mode in [LoadMode::Copy, LoadMode::Insert] {
+        let exporter = PostgresExporter {
+            conn_str: conn_str.clone(),
+            truncate: true,
+            load_mode,
+            batch_size: 2,
+            ..PostgresExporter::new(conn_str.clone())
+        };
+        exporter.export(&common::sample()).unwrap();
+
+        // A second run without truncation must not duplicate anything
+        let rerun = PostgresExporter { truncate: false, ..exporter };
+        rerun.export(&common::sample()).unwrap();
+
+        assert_eq!(count(&conn_str, "SELECT COUNT(*) FROM bridge_file"), 2, "{:?}", load_mode);
+        assert_eq!(count(&conn_str, "SELECT COUNT(*) FROM bridge_entry"), 4, "{:?}", load_mode);
+        assert_eq!(
+            count(&conn_str, "SELECT COUNT(*) FROM bridge_entry WHERE block = 'ru,cn' AND ratio IS NULL"),
+            1,
+            "{:?}",
+            load_mode
+        );
+    }
+}
+
+#[test]
+#[ignore]
+fn test_migrate_up_and_refuse_newer_schema() {
+    let _guard = DB_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    let conn_str = test_db();
+    execute(&conn_str, "DROP SCHEMA IF EXISTS bridge_norm CASCADE; DROP TABLE IF EXISTS bridge_entry, bridge_file, schema_migrations CASCADE");
+
+    let exporter = PostgresExporter::new(conn_str.clone());
+    assert_eq!(exporter.migrate(true).unwrap().len(), MIGRATIONS.len());
+    assert!(exporter.migration_status().unwrap().iter().all(|s| s.applied_at.is_none()));
+
+    assert_eq!(exporter.migrate(false).unwrap().len(), MIGRATIONS.len());
+    assert!(exporter.migrate(false).unwrap().is_empty());
+    assert!(exporter.migration_status().unwrap().iter().all(|s| s.applied_at.is_some()));
+
+    // A schema migrated by a newer binary must not be written to
+    execute(&conn_str, "INSERT INTO schema_migrations (version, name) VALUES (9999, 'from_the_future')");
+    let err = exporter.export(&common::sample()).unwrap_err();
+    assert!(err.to_string().contains("9999"), "{}", err);
+    assert_eq!(count(&conn_str, "SELECT COUNT(*) FROM bridge_file"), 0);
+
+    execute(&conn_str, "DELETE FROM schema_migrations WHERE version = 9999");
+}
+
+#[test]
+#[ignore]
+fn test_tls_modes_against_self_signed_server() {
+    common::setup();
+    let conn_str = std::env::var("BRIDGE_PARSER_TEST_TLS_DB").expect("BRIDGE_PARSER_TEST_TLS_DB must be set for the TLS test");
+    let ca = std::env::var("BRIDGE_PARSER_TEST_TLS_CA").expect("BRIDGE_PARSER_TEST_TLS_CA must be set for the TLS test");
+
+    let status = |conn_str: &str, mode: SslMode, root_cert: Option<&str>| {
+        PostgresExporter {
+            tls: TlsConfig {
+                mode: Some(mode),
+                root_cert: root_cert.map(Into::into),
+                ..TlsConfig::default()
+            },
+            ..PostgresExporter::new(conn_str.to_string())
+        }
+        .migration_status()
+    };
+
+    // Encryption without verification works with any certificate
+    assert!(status(&conn_str, SslMode::Require, None).is_ok());
+    // The self-signed CA is not among the default roots
+    assert!(status(&conn_str, SslMode::VerifyCa, None).is_err());
+    assert!(status(&conn_str, SslMode::VerifyFull, Some(&ca)).is_ok());
+
+    // The certificate is issued for localhost, not the IP address
+    let by_ip = conn_str.replace("localhost", "127.0.0.1");
+    assert!(status(&by_ip, SslMode::VerifyCa, Some(&ca)).is_ok());
+    assert!(status(&by_ip, SslMode::VerifyFull, Some(&ca)).is_err());
+}
+
+#[test]
+#[ignore]
+fn test_normalized_layout_matches_flat_views() {
+    let _guard = DB_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    let conn_str = test_db();
+
+    let flat = PostgresExporter {
+        truncate: true,
+        ..PostgresExporter::new(conn_str.clone())
+    };
+    flat.export(&common::sample()).unwrap();
+
+    let normalized = PostgresExporter {
+        truncate: true,
+        layout: Layout::Normalized,
+        batch_size: 2,
+        ..PostgresExporter::new(conn_str.clone())
+    };
+    normalized.export(&common::sample()[1..]).unwrap();
+    let append = PostgresExporter { truncate: false, ..normalized };
+    append.export(&common::sample()).unwrap();
+
+    assert_eq!(count(&conn_str, "SELECT COUNT(*) FROM bridge_norm.bridge"), 3);
+    assert_eq!(count(&conn_str, "SELECT COUNT(*) FROM bridge_norm.assignment"), 4);
+    assert_eq!(
+        count(
+            &conn_str,
+            "SELECT COUNT(*) FROM bridge_norm.bridge
+             WHERE fingerprint = '0004f8aea55fe852194674c8554d68cc5e7a5bba'
+               AND first_seen = '2022-04-09 00:29:37+00' AND last_seen = '2022-04-10 00:29:37+00'"
+        ),
+        1
+    );
+    assert_eq!(
+        count(&conn_str, "SELECT COUNT(*) FROM bridge_norm.assignment WHERE ip_versions = '{4,6}' AND blocklist IS NULL"),
+        2
+    );
+
+    // The compatibility views reproduce the flat tables row for row
+    let diff = |a: &str, b: &str| count(&conn_str, &format!("SELECT COUNT(*) FROM ((TABLE {a} EXCEPT TABLE {b}) UNION ALL (TABLE {b} EXCEPT TABLE {a})) d"));
+    assert_eq!(diff("public.bridge_entry", "bridge_norm.bridge_entry"), 0);
+    assert_eq!(diff("public.bridge_file", "bridge_norm.bridge_file"), 0);
+}
+
+#[test]
+#[ignore]
+fn test_normalized_reload_keeps_lookup_sequences() {
+    let _guard = DB_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    let conn_str = test_db();
+    let sequences = || {
+        count(
+            &conn_str,
+            "SELECT (SELECT last_value FROM bridge_norm.distribution_method_id_seq)
+                  + (SELECT last_value FROM bridge_norm.transport_id_seq)
+                  + (SELECT last_value FROM bridge_norm.state_id_seq)
+                  + (SELECT last_value FROM bridge_norm.bandwidth_id_seq)",
+        )
+    };
+
+    let exporter = PostgresExporter {
+        layout: Layout::Normalized,
+        ..PostgresExporter::new(conn_str.clone())
+    };
+    exporter.export(&common::sample()[..1]).unwrap();
+    let before = sequences();
+    exporter.export(&common::sample()[..1]).unwrap();
+    assert_eq!(sequences(), before);
+
+    assert_eq!(
+        count(
+            &conn_str,
+            "SELECT COUNT(*) FROM information_schema.columns
+             WHERE table_schema = 'bridge_norm' AND column_name LIKE '%id' AND data_type = 'smallint'"
+        ),
+        0
+    );
+}
+
+#[test]
+#[ignore]
+fn test_export_modes_report_inserted_updated_and_skipped() {
+    let _guard = DB_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    let conn_str = test_db();
+
+    for layout in [Layout::Flat, Layout::Normalized] {
+        let exporter = |mode: ExportMode, truncate: bool| PostgresExporter {
+            truncate,
+            layout,
+            mode,
+            ..PostgresExporter::new(conn_str.clone())
+        };
+
+        let stats = exporter(ExportMode::Append, true).load(&common::sample()).unwrap();
+        assert_eq!((stats.rows_inserted, stats.rows_updated, stats.rows_skipped), (4, 0, 0), "{:?}", layout);
+
+        let stats = exporter(ExportMode::SkipExisting, false).load(&common::sample()).unwrap();
+        assert_eq!(stats.files_skipped, 2, "{:?}", layout);
+        assert_eq!((stats.rows_inserted, stats.rows_updated, stats.rows_skipped), (0, 0, 4), "{:?}", layout);
+
+        // Unchanged rows are left alone, changed ones are updated in place
+        let mut changed = common::sample();
+        changed[0].lines[0].state = Some("dysfunctional".into());
+        let stats = exporter(ExportMode::Upsert, false).load(&changed).unwrap();
+        assert_eq!((stats.rows_inserted, stats.rows_updated, stats.rows_skipped), (0, 1, 3), "{:?}", layout);
+        let entries = match layout {
+            Layout::Flat => "bridge_entry",
+            Layout::Normalized => "bridge_norm.bridge_entry",
+        };
+        assert_eq!(count(&conn_str, &format!("SELECT COUNT(*) FROM {} WHERE state = 'dysfunctional'", entries)), 1);
+
+        // Dropping a line from a file removes it on replace
+        let mut shortened = common::sample();
+        shortened[0].lines.pop();
+        let stats = exporter(ExportMode::ReplaceFile, false).load(&shortened).unwrap();
+        assert_eq!((stats.rows_deleted, stats.rows_inserted), (4, 3), "{:?}", layout);
+        assert_eq!(count(&conn_str, &format!("SELECT COUNT(*) FROM {}", entries)), 3, "{:?}", layout);
+        assert_eq!(count(&conn_str, &format!("SELECT COUNT(*) FROM {} WHERE state = 'dysfunctional'", entries)), 0);
+    }
+}
+
+#[test]
+#[ignore]
+fn test_small_files_are_merged_in_shared_batches() {
+    let _guard = DB_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    let conn_str = test_db();
+
+    // One line per file, each with its own bridge
+    let files: Vec<_> = (0..50)
+        .map(|i| {
+            common::assignment(
+                &format!("2022-04-09 00:{:02}:00", i),
+                &[format!("{:040x} email transport=obfs4", i)],
+            )
+        })
+        .collect();
+
+    for layout in [Layout::Flat, Layout::Normalized] {
+        let exporter = |batch_size: usize| PostgresExporter {
+            truncate: true,
+            batch_size,
+            layout,
+            ..PostgresExporter::new(conn_str.clone())
+        };
+
+        let stats = exporter(1_000).load(&files).unwrap();
+        assert_eq!(stats.batches, 1, "{:?}", layout);
+        assert_eq!(stats.rows_inserted, 50, "{:?}", layout);
+
+        // Full batches are merged as they fill up, the rest on finish
+        let stats = exporter(20).load(&files).unwrap();
+        assert_eq!(stats.batches, 3, "{:?}", layout);
+        assert_eq!(stats.rows_inserted, 50, "{:?}", layout);
+    }
+}
+
+#[test]
+#[ignore]
+fn test_partitioned_export_and_prune() {
+    let _guard = DB_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    let conn_str = test_db();
+    let partitions = "SELECT COUNT(*) FROM pg_inherits WHERE inhparent = 'bridge_entry'::regclass";
+
+    // Rows already loaded are moved into monthly partitions; dependent views are rebuilt
+    let seen = PostgresExporter {
+        truncate: true,
+        views: vec![SummaryView::Seen],
+        ..PostgresExporter::new(conn_str.clone())
+    };
+    seen.export(&common::sample()).unwrap();
+    let partitioned = PostgresExporter {
+        partition: true,
+        mode: ExportMode::Upsert,
+        views: vec![SummaryView::Seen],
+        ..PostgresExporter::new(conn_str.clone())
+    };
+    let mut changed = common::sample();
+    changed[0].lines[0].state = Some("dysfunctional".into());
+    let stats = partitioned.load(&changed).unwrap();
+    assert_eq!((stats.rows_inserted, stats.rows_updated), (0, 1));
+    assert_eq!(count(&conn_str, "SELECT COUNT(*) FROM pg_class WHERE relname = 'bridge_entry' AND relkind = 'p'"), 1);
+    assert_eq!(count(&conn_str, partitions), 1);
+    assert_eq!(count(&conn_str, "SELECT COUNT(*) FROM bridge_entry_y2022m04"), 4);
+    assert_eq!(count(&conn_str, "SELECT COUNT(*) FROM bridge_fingerprint_seen"), 3);
+
+    // Later exports create the months they need, with or without the flag
+    let mut may = common::sample();
+    may.truncate(1);
+    may[0] = common::assignment("2022-05-01 00:00:00", &[
+        "0004f8aea55fe852194674c8554d68cc5e7a5bba email transport=vanilla",
+    ]);
+    PostgresExporter::new(conn_str.clone()).export(&may).unwrap();
+    assert_eq!(count(&conn_str, "SELECT COUNT(*) FROM bridge_entry_y2022m05"), 1);
+
+    let cutoff = NaiveDate::from_ymd_opt(2022, 5, 15).unwrap();
+    let listed = partitioned.prune(cutoff, &PruneAction::Drop, true).unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!((listed[0].name.as_str(), listed[0].rows), ("bridge_entry_y2022m04", 4));
+    assert_eq!(count(&conn_str, partitions), 2);
+
+    // Detached partitions keep their rows but leave bridge_entry
+    partitioned.prune(cutoff, &PruneAction::Detach, false).unwrap();
+    assert_eq!(count(&conn_str, partitions), 1);
+    assert_eq!(count(&conn_str, "SELECT COUNT(*) FROM bridge_entry"), 1);
+    assert_eq!(count(&conn_str, "SELECT COUNT(*) FROM bridge_entry_y2022m04"), 4);
+    let err = partitioned.export(&common::sample()).unwrap_err();
+    assert!(err.to_string().contains("detached"), "{}", err);
+    execute(&conn_str, "DROP TABLE bridge_entry_y2022m04");
+
+    #[cfg(feature = "parquet_export")]
+    {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        partitioned.export(&common::sample()).unwrap();
+        let dir = common::temp_path("archive");
+        partitioned.prune(cutoff, &PruneAction::Archive(dir.clone()), false).unwrap();
+        let file = std::fs::File::open(dir.join("bridge_entry_y2022m04.parquet")).unwrap();
+        assert_eq!(SerializedFileReader::new(file).unwrap().metadata().file_metadata().num_rows(), 4);
+        assert_eq!(count(&conn_str, partitions), 1);
+    }
+
+    partitioned.prune(NaiveDate::from_ymd_opt(2022, 6, 1).unwrap(), &PruneAction::Drop, false).unwrap();
+    assert_eq!(count(&conn_str, partitions), 0);
+
+    // Back to the plain table the other tests expect
+    execute(&conn_str, &format!("DROP TABLE bridge_entry CASCADE; {}; {}", MIGRATIONS[0].sql, MIGRATIONS[1].sql));
+}
+
+#[test]
+#[ignore]
+fn test_summary_views_are_created_and_refreshed() {
+    let _guard = DB_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    let conn_str = test_db();
+    let may = common::assignment("2022-05-01 00:00:00", &[
+        "0004f8aea55fe852194674c8554d68cc5e7a5bba email transport=vanilla state=functional",
+        "0f0e0d0c0b0a09080706050403020100f0e0d0c0 settings transport=obfs4",
+    ]);
+
+    for layout in [Layout::Flat, Layout::Normalized] {
+        let schema = match layout {
+            Layout::Flat => "public",
+            Layout::Normalized => "bridge_norm",
+        };
+        let exporter = PostgresExporter {
+            truncate: true,
+            layout,
+            views: SummaryView::ALL.to_vec(),
+            ..PostgresExporter::new(conn_str.clone())
+        };
+        exporter.export(&common::sample()).unwrap();
+
+        let daily = format!("SELECT COUNT(*) FROM {schema}.bridge_daily_counts");
+        let pool = format!("SELECT SUM(bridges)::bigint FROM {schema}.bridge_pool_composition");
+        let seen = format!("SELECT COUNT(*) FROM {schema}.bridge_fingerprint_seen");
+        assert_eq!(count(&conn_str, &daily), 4, "{:?}", layout);
+        assert_eq!(count(&conn_str, &pool), 1, "{:?}", layout);
+        assert_eq!(count(&conn_str, &seen), 3, "{:?}", layout);
+
+        // Later loads refresh the existing views, including rows with NULL keys
+        let append = PostgresExporter { truncate: false, ..exporter };
+        append.export(std::slice::from_ref(&may)).unwrap();
+        assert_eq!(count(&conn_str, &daily), 6, "{:?}", layout);
+        assert_eq!(count(&conn_str, &pool), 2, "{:?}", layout);
+        assert_eq!(count(&conn_str, &seen), 4, "{:?}", layout);
+        assert_eq!(
+            count(
+                &conn_str,
+                &format!(
+                    "SELECT files FROM {schema}.bridge_fingerprint_seen
+                     WHERE fingerprint = '0004f8aea55fe852194674c8554d68cc5e7a5bba'
+                       AND first_seen = '2022-04-09 00:29:37' AND last_seen = '2022-05-01 00:00:00'"
+                )
+            ),
+            3,
+            "{:?}",
+            layout
+        );
+
+        execute(
+            &conn_str,
+            &format!(
+                "DROP MATERIALIZED VIEW {schema}.bridge_daily_counts, {schema}.bridge_pool_composition,
+                 {schema}.bridge_fingerprint_seen"
+            ),
+        );
+    }
+}
@@ -0,0 +1,35 @@
+//! Tests for the Prometheus textfile exporter
+
+use bridge_parser::exporter::{Exporter, PrometheusExporter};
+
+mod common;
+
+#[test]
+fn test_prometheus_textfile_describes_latest_assignment() {
+    common::setup();
+    let path = common::temp_path("pool.prom");
+    let latest = common::assignment("2022-04-10 00:00:00", &[
+        "0004f8aea55fe852194674c8554d68cc5e7a5bba https transport=obfs4 ip=4 state=functional blocklist=ru,cn",
+        "00e1ae6cb75e47e363e6aef9f67a49c0e854fde7 https transport=obfs4 ip=4 state=functional blocklist=ru",
+        "01bd7a8b8c7b8c7d8e3f6a4b2c1d0e9f8a7b6c5d moat ip=4,6",
+    ]);
+    let older = common::assignment("2022-04-09 00:00:00", &[
+        "0004f8aea55fe852194674c8554d68cc5e7a5bba email transport=vanilla ip=6",
+    ]);
+    PrometheusExporter::new(path.clone()).export(&[latest, older]).unwrap();
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    let samples: Vec<&str> = text.lines().filter(|l| !l.starts_with('#')).collect();
+    assert_eq!(samples, [
+        r#"bridge_pool_bridges{method="https",transport="obfs4",state="functional",ip="4"} 2"#,
+        r#"bridge_pool_bridges{method="moat",transport="unknown",state="unknown",ip="4,6"} 1"#,
+        r#"bridge_pool_blocked_bridges{country="cn"} 1"#,
+        r#"bridge_pool_blocked_bridges{country="ru"} 2"#,
+        "bridge_pool_published_timestamp_seconds 1649548800",
+    ]);
+    assert!(text.contains("# TYPE bridge_pool_bridges gauge\n"));
+
+    // Without assignments the previous composition is kept
+    PrometheusExporter::new(path.clone()).export(&[]).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), text);
+}
@@ -0,0 +1,72 @@
+//! Tests for the SQLite exporter (requires `--features sqlite_export`)
+#![cfg(feature = "sqlite_export")]
+
+use bridge_parser::exporter::{Exporter, SqliteExporter};
+use rusqlite::Connection;
+
+mod common;
+
+#[test]
+fn test_sqlite_export_is_idempotent_across_batches() {
+    common::setup();
+    let path = common::temp_path("entries.sqlite");
+    let _ = std::fs::remove_file(&path);
+
+    let exporter = SqliteExporter { batch_size: 2, ..SqliteExporter::new(path.clone()) };
+    exporter.export(&common::sample()).unwrap();
+    exporter.export(&common::sample()).unwrap();
+
+    let conn = Connection::open(&path).unwrap();
+    let count = |sql: &str| conn.query_row(sql, [], |r| r.get::<_, i64>(0)).unwrap();
+    assert_eq!(count("SELECT COUNT(*) FROM bridge_file"), 2);
+    assert_eq!(count("SELECT COUNT(*) FROM bridge_entry"), 4);
+    assert_eq!(count("SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND name LIKE 'bridge_entry_%_idx'"), 2);
+
+    let (published, ratio): (String, f64) = conn
+        .query_row("SELECT published, ratio FROM bridge_entry WHERE method = 'https'", [], |r| Ok((r.get(0)?, r.get(1)?)))
+        .unwrap();
+    assert_eq!(published, "2022-04-09 00:29:37.000");
+    assert!((ratio - 1.49).abs() < 1e-6);
+}
+
+#[test]
+fn test_sqlite_new_databases_are_moved_into_place() {
+    use bridge_parser::exporter::IfExists;
+
+    common::setup();
+    let path = common::temp_path("versioned.sqlite");
+    let versioned = common::temp_path("versioned.1.sqlite");
+    for p in [&path, &versioned] {
+        let _ = std::fs::remove_file(p);
+    }
+
+    let exporter = |if_exists| SqliteExporter { if_exists, ..SqliteExporter::new(path.clone()) };
+    exporter(IfExists::Refuse).export(&common::sample()).unwrap();
+    assert!(exporter(IfExists::Refuse).export(&common::sample()).is_err());
+    exporter(IfExists::Version).export(&common::sample()[..1]).unwrap();
+
+    let count = |db: &std::path::Path| {
+        Connection::open(db).unwrap().query_row("SELECT COUNT(*) FROM bridge_entry", [], |r| r.get::<_, i64>(0)).unwrap()
+    };
+    assert_eq!(count(&path), 4);
+    assert_eq!(count(&versioned), 3);
+    assert!(!path.with_extension("sqlite-wal").exists());
+}
+
+#[test]
+fn test_sqlite_leaves_only_the_database_file() {
+    common::setup();
+    let dir = common::temp_path("sqlite_dir");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("bridges.db");
+
+    // Once into a new database, once adding to the existing one
+    SqliteExporter::new(path.clone()).export(&common::sample()).unwrap();
+    SqliteExporter::new(path.clone()).export(&common::sample()).unwrap();
+
+    let files: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+    assert_eq!(files, ["bridges.db"]);
+    let mode: String = Connection::open(&path).unwrap().query_row("PRAGMA journal_mode", [], |r| r.get(0)).unwrap();
+    assert_eq!(mode, "delete");
+}